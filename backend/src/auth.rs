@@ -0,0 +1,75 @@
+use argon2::{
+    Argon2,
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+};
+use axum::{
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use rand::RngCore;
+
+use crate::data::SharedState;
+use crate::error::ApiError;
+
+const TOKEN_BYTES: usize = 32;
+
+/// The user a request was made by, extracted from its `Authorization: Bearer <token>` header
+#[derive(Clone, Copy, Debug)]
+pub struct AuthUser {
+    pub id: u32,
+}
+
+impl<S> FromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.strip_prefix("Bearer "))
+            .ok_or(ApiError::Unauthorized)?;
+
+        let state = parts
+            .extensions
+            .get::<SharedState>()
+            .ok_or(ApiError::Unauthorized)?;
+
+        match state.read().unwrap().sessions.get(token) {
+            Some(id) => Ok(AuthUser { id: *id }),
+            None => Err(ApiError::Unauthorized),
+        }
+    }
+}
+
+pub fn hash_password(password: &str) -> Result<String, ApiError> {
+    let mut salt = [0u8; 16];
+    rand::rng().fill_bytes(&mut salt);
+
+    let salt = SaltString::encode_b64(&salt).map_err(|e| ApiError::Internal(e.to_string()))?;
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| ApiError::Internal(e.to_string()))?;
+
+    Ok(hash.to_string())
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// A random hex encoded session token
+pub fn new_token() -> String {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    rand::rng().fill_bytes(&mut bytes);
+
+    bytes.iter().map(|x| format!("{x:02x}")).collect()
+}
@@ -0,0 +1,313 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::{Item, Stats, Unit};
+use crate::items;
+use crate::status::{StatusKind, StatusSpec};
+
+/// A monster the party fights. Missing fields fall back to their defaults,
+/// so fights saved before monsters had their own type still load.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Monster {
+    pub name: String,
+    pub family: MonsterFamily,
+    pub unit: Unit,
+    pub damage: i32,
+    pub experience: u32,
+    pub behaviour: Behaviour,
+    pub loot: Vec<LootEntry>,
+    pub fled: bool,
+    /// Passed on to party members it hits
+    pub inflicts: Option<StatusSpec>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MonsterFamily {
+    #[default]
+    Vermin,
+    Feline,
+    Insect,
+    Mustelid,
+}
+
+/// How a monster picks what to do on its turn, see `Behaviour::strategy`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Behaviour {
+    #[default]
+    Aggressive,
+    Cunning,
+    Support,
+    Cowardly,
+    Boss,
+}
+
+/// An item a monster can drop, `chance` is in percent
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LootEntry {
+    pub item: Item,
+    pub chance: u8,
+}
+
+/// The level one stat block of a kind of monster
+pub struct MonsterTemplate {
+    pub name: &'static str,
+    pub family: MonsterFamily,
+    pub health: i32,
+    pub energy: i32,
+    pub damage: i32,
+    pub speed: i32,
+    pub evasion: i32,
+    pub experience: u32,
+    pub behaviour: Behaviour,
+    pub boss: bool,
+    pub inflicts: Option<StatusSpec>,
+    pub loot: &'static [(&'static str, u8)],
+}
+
+/// Percent chance of a monster landing a critical hit
+const MONSTER_CRIT: i32 = 5;
+
+const fn poison(potency: i32, turns: u16) -> StatusSpec {
+    StatusSpec {
+        kind: StatusKind::Poison,
+        potency,
+        turns,
+    }
+}
+
+pub const FAMILIES: [MonsterFamily; 4] = [
+    MonsterFamily::Vermin,
+    MonsterFamily::Feline,
+    MonsterFamily::Insect,
+    MonsterFamily::Mustelid,
+];
+
+pub const BESTIARY: &[MonsterTemplate] = &[
+    MonsterTemplate {
+        name: "Sewer Rat",
+        family: MonsterFamily::Vermin,
+        health: 6,
+        energy: 4,
+        damage: 2,
+        speed: 4,
+        evasion: 5,
+        experience: 8,
+        behaviour: Behaviour::Aggressive,
+        boss: false,
+        inflicts: None,
+        loot: &[("Cheese Rind", 40)],
+    },
+    MonsterTemplate {
+        name: "Plague Rat",
+        family: MonsterFamily::Vermin,
+        health: 5,
+        energy: 8,
+        damage: 1,
+        speed: 4,
+        evasion: 5,
+        experience: 10,
+        behaviour: Behaviour::Support,
+        boss: false,
+        inflicts: Some(poison(1, 3)),
+        loot: &[("Cheese Rind", 30), ("Herb Bundle", 20)],
+    },
+    MonsterTemplate {
+        name: "Rat King",
+        family: MonsterFamily::Vermin,
+        health: 22,
+        energy: 12,
+        damage: 3,
+        speed: 4,
+        evasion: 5,
+        experience: 40,
+        behaviour: Behaviour::Boss,
+        boss: true,
+        inflicts: None,
+        loot: &[("Crown of Tails", 50), ("Cheese Wheel", 80)],
+    },
+    MonsterTemplate {
+        name: "Alley Cat",
+        family: MonsterFamily::Feline,
+        health: 8,
+        energy: 6,
+        damage: 3,
+        speed: 6,
+        evasion: 15,
+        experience: 12,
+        behaviour: Behaviour::Cunning,
+        boss: false,
+        inflicts: None,
+        loot: &[("Cat Whisker", 35)],
+    },
+    MonsterTemplate {
+        name: "Scrawny Kitten",
+        family: MonsterFamily::Feline,
+        health: 4,
+        energy: 4,
+        damage: 1,
+        speed: 6,
+        evasion: 15,
+        experience: 5,
+        behaviour: Behaviour::Cowardly,
+        boss: false,
+        inflicts: None,
+        loot: &[("Ball of Yarn", 25)],
+    },
+    MonsterTemplate {
+        name: "One-Eyed Tomcat",
+        family: MonsterFamily::Feline,
+        health: 26,
+        energy: 10,
+        damage: 4,
+        speed: 6,
+        evasion: 5,
+        experience: 45,
+        behaviour: Behaviour::Boss,
+        boss: true,
+        inflicts: None,
+        loot: &[("Tomcat Collar", 50), ("Cat Whisker", 90)],
+    },
+    MonsterTemplate {
+        name: "Cockroach Swarm",
+        family: MonsterFamily::Insect,
+        health: 7,
+        energy: 3,
+        damage: 2,
+        speed: 2,
+        evasion: 5,
+        experience: 7,
+        behaviour: Behaviour::Aggressive,
+        boss: false,
+        inflicts: None,
+        loot: &[("Chitin Shard", 30)],
+    },
+    MonsterTemplate {
+        name: "Dung Beetle",
+        family: MonsterFamily::Insect,
+        health: 10,
+        energy: 4,
+        damage: 2,
+        speed: 2,
+        evasion: 5,
+        experience: 9,
+        behaviour: Behaviour::Cowardly,
+        boss: false,
+        inflicts: None,
+        loot: &[("Chitin Shard", 50)],
+    },
+    MonsterTemplate {
+        name: "Roach Queen",
+        family: MonsterFamily::Insect,
+        health: 20,
+        energy: 14,
+        damage: 3,
+        speed: 2,
+        evasion: 5,
+        experience: 38,
+        behaviour: Behaviour::Boss,
+        boss: true,
+        inflicts: Some(poison(2, 3)),
+        loot: &[("Royal Jelly", 60), ("Chitin Shard", 90)],
+    },
+    MonsterTemplate {
+        name: "Rabid Ferret",
+        family: MonsterFamily::Mustelid,
+        health: 9,
+        energy: 6,
+        damage: 3,
+        speed: 5,
+        evasion: 10,
+        experience: 12,
+        behaviour: Behaviour::Aggressive,
+        boss: false,
+        inflicts: None,
+        loot: &[("Ferret Fang", 30)],
+    },
+    MonsterTemplate {
+        name: "Weasel Shaman",
+        family: MonsterFamily::Mustelid,
+        health: 7,
+        energy: 12,
+        damage: 2,
+        speed: 5,
+        evasion: 10,
+        experience: 14,
+        behaviour: Behaviour::Support,
+        boss: false,
+        inflicts: None,
+        loot: &[("Herb Bundle", 40)],
+    },
+    MonsterTemplate {
+        name: "Stoat Warlord",
+        family: MonsterFamily::Mustelid,
+        health: 24,
+        energy: 12,
+        damage: 4,
+        speed: 5,
+        evasion: 5,
+        experience: 42,
+        behaviour: Behaviour::Boss,
+        boss: true,
+        inflicts: None,
+        loot: &[("Warlord's Banner", 50), ("Ferret Fang", 90)],
+    },
+];
+
+/// The regular, non boss, monsters of a family
+pub fn regulars(family: MonsterFamily) -> Vec<&'static MonsterTemplate> {
+    BESTIARY.iter().filter(|x| x.family == family && !x.boss).collect()
+}
+
+pub fn bosses(family: MonsterFamily) -> Vec<&'static MonsterTemplate> {
+    BESTIARY.iter().filter(|x| x.family == family && x.boss).collect()
+}
+
+impl MonsterTemplate {
+    /// A monster of this kind, grown to match a party of `level`
+    pub fn spawn(&self, level: u32) -> Monster {
+        let extra = level.max(1) as i32 - 1;
+        let stats = Stats {
+            health: self.health + extra * self.health / 3,
+            energy: self.energy + extra,
+            speed: self.speed,
+            crit: MONSTER_CRIT,
+            evasion: self.evasion,
+            ..Stats::default()
+        };
+
+        Monster {
+            name: self.name.to_string(),
+            family: self.family,
+            unit: Unit {
+                stats,
+                max_stats: stats,
+            },
+            damage: self.damage + extra / 2,
+            experience: self.experience * level.max(1),
+            behaviour: self.behaviour,
+            loot: self
+                .loot
+                .iter()
+                .map(|(name, chance)| LootEntry {
+                    item: items::item(name),
+                    chance: *chance,
+                })
+                .collect(),
+            fled: false,
+            inflicts: self.inflicts,
+        }
+    }
+}
+
+impl fmt::Display for MonsterFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MonsterFamily::Vermin => write!(f, "Vermin"),
+            MonsterFamily::Feline => write!(f, "Feline"),
+            MonsterFamily::Insect => write!(f, "Insect"),
+            MonsterFamily::Mustelid => write!(f, "Mustelid"),
+        }
+    }
+}
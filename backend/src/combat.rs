@@ -0,0 +1,596 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::bestiary::{Behaviour, Monster};
+use crate::data::{Character, ItemKind, Stats};
+use crate::error::ApiError;
+use crate::monster_ai::{HEAL_ENERGY_COST, MonsterMove};
+use crate::quest_data::{Combat, CombatAction, CombatLogEntry, CombatStatus, Cooldown, TurnAction};
+use crate::skills::{Skill, SkillEffect, SkillTarget};
+use crate::status::{Combatant, StatusEffect, StatusKind};
+
+pub const ATTACK_ENERGY_COST: i32 = 1;
+/// Damage of an attack with nothing in hand, the equipped weapon adds its attack on top
+const UNARMED_DAMAGE: i32 = 1;
+const EXHAUSTED_DAMAGE: i32 = 1;
+
+/// Queues the action of a party member. The turn plays out once every living member has chosen one,
+/// or sooner through `end_turn`.
+pub fn submit_action(members: &mut [Character], combat: &mut Combat, action: TurnAction) -> Result<(), ApiError> {
+    if combat.status != CombatStatus::Ongoing {
+        return Err(ApiError::BadRequest("The fight is already over".to_string()));
+    }
+
+    let member = match members.iter().find(|x| x.id == action.character_id) {
+        Some(member) if member.unit.stats.health > 0 => member,
+        Some(_) => return Err(ApiError::BadRequest("Knocked out characters can't act".to_string())),
+        None => return Err(ApiError::NotFound(format!("character {} in this fight", action.character_id))),
+    };
+
+    match &action.action {
+        CombatAction::WeaponAttack => {}
+        CombatAction::UseItem(item) => match member.inventory.get(&item.name) {
+            Some(x) if x.kind == ItemKind::Consumable => {}
+            Some(_) => return Err(ApiError::BadRequest(format!("{} can't be used in a fight", item.name))),
+            None => return Err(ApiError::NotFound(format!("{} in {}'s inventory", item.name, member.name))),
+        },
+        CombatAction::UseSkill(name) => {
+            let skill = member
+                .class
+                .skill(name)
+                .ok_or(ApiError::NotFound(format!("{name} among {} skills", member.class)))?;
+
+            if member.unit.stats.energy < skill.energy {
+                return Err(ApiError::BadRequest(format!("{name} needs {} energy", skill.energy)));
+            }
+
+            if let Some(turn) = combat.cooldown(member.id, name) {
+                return Err(ApiError::BadRequest(format!("{name} is ready again on turn {turn}")));
+            }
+
+            if skill.target == SkillTarget::Ally && ally_index(members, action.target).is_none() {
+                return Err(ApiError::BadRequest(format!("{name} needs an ally still standing")));
+            }
+        }
+    }
+
+    combat.pending.retain(|x| x.character_id != action.character_id);
+    combat.pending.push(action);
+
+    let everyone_ready = members
+        .iter()
+        .filter(|x| x.unit.stats.health > 0)
+        .all(|x| combat.pending.iter().any(|a| a.character_id == x.id));
+
+    if everyone_ready {
+        resolve_turn(members, combat);
+    }
+
+    Ok(())
+}
+
+/// Plays out the turn without waiting on the members who haven't chosen an action yet,
+/// they attack the first monster still standing. Only a member who has already chosen can do this.
+pub fn end_turn(members: &mut [Character], combat: &mut Combat, character_id: u32) -> Result<(), ApiError> {
+    if combat.status != CombatStatus::Ongoing {
+        return Err(ApiError::BadRequest("The fight is already over".to_string()));
+    }
+
+    if !combat.pending.iter().any(|x| x.character_id == character_id) {
+        return Err(ApiError::BadRequest("Choose an action before ending the turn".to_string()));
+    }
+
+    for member in members.iter().filter(|x| x.unit.stats.health > 0) {
+        if !combat.pending.iter().any(|x| x.character_id == member.id) {
+            combat.pending.push(TurnAction {
+                character_id: member.id,
+                action: CombatAction::WeaponAttack,
+                target: 0,
+            });
+        }
+    }
+
+    resolve_turn(members, combat);
+
+    Ok(())
+}
+
+/// Crits deal this many times the damage
+const CRIT_MULTIPLIER: i32 = 2;
+
+/// Someone taking their turn in combat, an index into the party or `Combat.monsters`
+#[derive(Clone, Copy, Debug)]
+enum Actor {
+    Member(usize),
+    Monster(usize),
+}
+
+/// What every action of a turn shares while the turn plays out
+struct TurnContext {
+    rng: ChaCha8Rng,
+    log: Vec<String>,
+    effects: Vec<StatusEffect>,
+}
+
+/// Plays out a full turn. Everyone acts in order of speed, party members with their chosen action
+/// and monsters following their behaviour, then status effects tick.
+/// Rolls are seeded from the fight, so a turn always plays out the same.
+pub fn resolve_turn(members: &mut [Character], combat: &mut Combat) {
+    combat.turn += 1;
+    let turn = combat.turn;
+    let mut pending = std::mem::take(&mut combat.pending);
+    let mut ctx = TurnContext {
+        rng: ChaCha8Rng::seed_from_u64(combat.seed.wrapping_add(turn as u64)),
+        log: vec![],
+        effects: std::mem::take(&mut combat.effects),
+    };
+
+    for actor in turn_order(members, &combat.monsters) {
+        if party_down(members) || monsters_gone(&combat.monsters) {
+            break;
+        }
+
+        match actor {
+            Actor::Member(i) => {
+                let member = &members[i];
+                let action = match pending.iter().position(|x| x.character_id == member.id) {
+                    Some(index) if member.unit.stats.health > 0 => pending.swap_remove(index),
+                    _ => continue,
+                };
+
+                if take_stun(&mut ctx, Combatant::Member(member.id)) {
+                    ctx.log.push(format!("{} is stunned and loses their turn", member.name));
+                    continue;
+                }
+
+                member_turn(members, i, combat, action, &mut ctx);
+            }
+            Actor::Monster(i) => {
+                if !combat.monsters[i].is_active() {
+                    continue;
+                }
+
+                if take_stun(&mut ctx, Combatant::Monster(i)) {
+                    ctx.log.push(format!("{} is stunned and loses its turn", combat.monsters[i].name));
+                    continue;
+                }
+
+                let action = combat.monsters[i].behaviour.strategy().choose(i, &combat.monsters, members);
+                monster_turn(members, &mut combat.monsters, i, action, &mut ctx);
+            }
+        }
+    }
+
+    tick_effects(members, &mut combat.monsters, &mut ctx);
+
+    if monsters_gone(&combat.monsters) {
+        combat.status = CombatStatus::Victory;
+        ctx.log.push("The party is victorious!".to_string());
+    } else if party_down(members) {
+        combat.status = CombatStatus::Wipe;
+        ctx.log.push("The party has been wiped out...".to_string());
+    }
+
+    if combat.status == CombatStatus::Ongoing {
+        combat.effects = ctx.effects;
+    } else {
+        for member in members.iter_mut() {
+            member.unit.reset_combat_stats();
+        }
+    }
+
+    combat
+        .log
+        .extend(ctx.log.into_iter().map(|message| CombatLogEntry { turn, message }));
+}
+
+/// Everyone in the fight, fastest first. The party goes first on a tie.
+fn turn_order(members: &[Character], monsters: &[Monster]) -> Vec<Actor> {
+    let mut order: Vec<(i32, Actor)> = members
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (x.unit.stats.speed, Actor::Member(i)))
+        .chain(monsters.iter().enumerate().map(|(i, x)| (x.unit.stats.speed, Actor::Monster(i))))
+        .collect();
+
+    order.sort_by_key(|(speed, _)| std::cmp::Reverse(*speed));
+    order.into_iter().map(|(_, actor)| actor).collect()
+}
+
+fn party_down(members: &[Character]) -> bool {
+    members.iter().all(|x| x.unit.stats.health <= 0)
+}
+
+fn monsters_gone(monsters: &[Monster]) -> bool {
+    monsters.iter().all(|x| !x.is_active())
+}
+
+/// Rolls an attack against the defender's evasion and the attacker's crit chance.
+/// Returns the damage dealt and whether it was a crit, or `None` on a miss.
+fn roll_damage(rng: &mut ChaCha8Rng, damage: i32, attacker: &Stats, defender: &Stats) -> Option<(i32, bool)> {
+    if rng.random_range(0..100) < defender.evasion {
+        return None;
+    }
+
+    let damage = (damage - defender.defense).max(1);
+    if rng.random_range(0..100) < attacker.crit {
+        Some((damage * CRIT_MULTIPLIER, true))
+    } else {
+        Some((damage, false))
+    }
+}
+
+/// Carries out the action the party member at `index` chose
+fn member_turn(members: &mut [Character], index: usize, combat: &mut Combat, action: TurnAction, ctx: &mut TurnContext) {
+    let member = &mut members[index];
+
+    match &action.action {
+        CombatAction::WeaponAttack => {
+            let target = match target_index(&combat.monsters, action.target) {
+                Some(i) => i,
+                None => return,
+            };
+
+            let damage = if member.unit.stats.energy >= ATTACK_ENERGY_COST {
+                member.unit.stats.energy -= ATTACK_ENERGY_COST;
+                UNARMED_DAMAGE + member.unit.stats.attack
+            } else {
+                EXHAUSTED_DAMAGE
+            };
+
+            strike(&member.name, &member.unit.stats, &mut combat.monsters[target], target, damage, ctx);
+        }
+        CombatAction::UseItem(item) => match member.inventory.take(&item.name) {
+            Some(item) => {
+                member.unit.restore(item.effect);
+                ctx.log.push(format!(
+                    "{} uses {} and recovers {} health and {} energy",
+                    member.name, item.name, item.effect.health, item.effect.energy
+                ));
+            }
+            None => ctx.log.push(format!("{} fumbles for {}, but has none left", member.name, item.name)),
+        },
+        CombatAction::UseSkill(name) => {
+            let skill = match member.class.skill(name) {
+                Some(x) => x,
+                None => return,
+            };
+
+            if member.unit.stats.energy < skill.energy {
+                ctx.log.push(format!("{} is too exhausted to use {name}", member.name));
+                return;
+            }
+
+            member.unit.stats.energy -= skill.energy;
+            combat.cooldowns.retain(|x| x.character_id != member.id || x.skill != skill.name);
+            combat.cooldowns.push(Cooldown {
+                character_id: member.id,
+                skill: skill.name.to_string(),
+                ready_turn: combat.turn + skill.cooldown + 1,
+            });
+
+            use_skill(members, index, combat, skill, action.target, ctx);
+        }
+    }
+}
+
+/// Applies the effect of a skill the member at `index` has paid for
+fn use_skill(members: &mut [Character], index: usize, combat: &mut Combat, skill: &Skill, target: usize, ctx: &mut TurnContext) {
+    let name = members[index].name.clone();
+    let stats = members[index].unit.stats;
+
+    match skill.target {
+        SkillTarget::Enemy => {
+            ctx.log.push(format!("{name} uses {}", skill.name));
+            if let Some(target) = target_index(&combat.monsters, target) {
+                skill_on_monster(&name, &stats, &mut combat.monsters[target], target, skill.effect, ctx);
+            }
+        }
+        SkillTarget::AllEnemies => {
+            ctx.log.push(format!("{name} uses {} on every monster", skill.name));
+            for (i, monster) in combat.monsters.iter_mut().enumerate().filter(|(_, x)| x.is_active()) {
+                skill_on_monster(&name, &stats, monster, i, skill.effect, ctx);
+            }
+        }
+        SkillTarget::Ally => {
+            let ally = match ally_index(members, target) {
+                Some(i) => &mut members[i],
+                None => return,
+            };
+
+            match skill.effect {
+                SkillEffect::Heal(amount) => {
+                    ally.unit.restore(Stats {
+                        health: amount,
+                        ..Stats::default()
+                    });
+                    ctx.log.push(format!("{name} uses {} and heals {} for {amount} health", skill.name, ally.name));
+                }
+                SkillEffect::Buff(bonus) => {
+                    let stats = &mut ally.unit.stats;
+                    stats.attack += bonus.attack;
+                    stats.defense += bonus.defense;
+                    stats.speed += bonus.speed;
+                    stats.crit += bonus.crit;
+                    stats.evasion += bonus.evasion;
+                    ctx.log.push(format!("{name} uses {} on {}", skill.name, ally.name));
+                }
+                SkillEffect::Status(spec) => {
+                    ctx.log.push(format!("{name} uses {} on {}", skill.name, ally.name));
+                    inflict(ctx, spec.on(Combatant::Member(ally.id)), &ally.name);
+                }
+                SkillEffect::Damage(_) => {}
+            }
+        }
+    }
+}
+
+fn skill_on_monster(name: &str, stats: &Stats, monster: &mut Monster, index: usize, effect: SkillEffect, ctx: &mut TurnContext) {
+    match effect {
+        SkillEffect::Damage(power) => strike(name, stats, monster, index, power + stats.attack, ctx),
+        SkillEffect::Status(spec) => inflict(ctx, spec.on(Combatant::Monster(index)), &monster.name),
+        SkillEffect::Heal(_) | SkillEffect::Buff(_) => {}
+    }
+}
+
+/// A party member attacking the monster at `index`
+fn strike(name: &str, attacker: &Stats, monster: &mut Monster, index: usize, damage: i32, ctx: &mut TurnContext) {
+    let (damage, crit) = match roll_damage(&mut ctx.rng, damage, attacker, &monster.unit.stats) {
+        Some(x) => x,
+        None => {
+            ctx.log.push(format!("{} dodges {name}'s attack", monster.name));
+            return;
+        }
+    };
+
+    ctx.log.push(hit_message(name, &monster.name, damage, crit));
+    let damage = absorb(ctx, Combatant::Monster(index), &monster.name, damage);
+
+    let phase = monster.phase();
+    monster.unit.stats.health = (monster.unit.stats.health - damage).max(0);
+
+    if monster.unit.stats.health == 0 {
+        ctx.log.push(format!("{} is defeated", monster.name));
+    } else if monster.behaviour == Behaviour::Boss && monster.phase() != phase {
+        ctx.log.push(format!("{} flies into a rage", monster.name));
+    }
+}
+
+/// Carries out the move a monster's strategy picked
+fn monster_turn(members: &mut [Character], monsters: &mut [Monster], index: usize, action: MonsterMove, ctx: &mut TurnContext) {
+    let attacker = monsters[index].clone();
+
+    match action {
+        MonsterMove::Attack { target, damage } => {
+            if let Some(target) = members.get_mut(target) {
+                hit(target, &attacker, damage, ctx);
+            }
+        }
+        MonsterMove::AttackAll { damage } => {
+            ctx.log.push(format!("{} lashes out at the whole party", attacker.name));
+            for target in members.iter_mut().filter(|x| x.unit.stats.health > 0) {
+                hit(target, &attacker, damage, ctx);
+            }
+        }
+        MonsterMove::Heal { target, amount } => {
+            monsters[index].unit.stats.energy -= HEAL_ENERGY_COST;
+            let ally = &mut monsters[target];
+            ally.unit.stats.health = (ally.unit.stats.health + amount).min(ally.unit.max_stats.health);
+            ctx.log.push(format!("{} heals {} for {amount} health", attacker.name, ally.name));
+        }
+        MonsterMove::Flee => {
+            monsters[index].fled = true;
+            ctx.log.push(format!("{} flees the fight", attacker.name));
+        }
+        MonsterMove::Wait => {}
+    }
+}
+
+/// A monster attacking a party member, their defense softens the blow but never shrugs it off entirely.
+/// Monsters with a status to inflict pass it on when they land a hit.
+fn hit(target: &mut Character, attacker: &Monster, damage: i32, ctx: &mut TurnContext) {
+    let (damage, crit) = match roll_damage(&mut ctx.rng, damage, &attacker.unit.stats, &target.unit.stats) {
+        Some(x) => x,
+        None => {
+            ctx.log.push(format!("{} dodges {}'s attack", target.name, attacker.name));
+            return;
+        }
+    };
+
+    ctx.log.push(hit_message(&attacker.name, &target.name, damage, crit));
+    let damage = absorb(ctx, Combatant::Member(target.id), &target.name, damage);
+    target.unit.stats.health = (target.unit.stats.health - damage).max(0);
+
+    if target.unit.stats.health == 0 {
+        ctx.log.push(format!("{} is knocked out", target.name));
+    } else if let Some(spec) = attacker.inflicts {
+        inflict(ctx, spec.on(Combatant::Member(target.id)), &target.name);
+    }
+}
+
+fn hit_message(attacker: &str, target: &str, damage: i32, crit: bool) -> String {
+    if crit {
+        format!("{attacker} lands a critical hit on {target} for {damage} damage")
+    } else {
+        format!("{attacker} hits {target} for {damage} damage")
+    }
+}
+
+/// Puts a status effect on its target. Landing one the target already has refreshes it instead of stacking.
+fn inflict(ctx: &mut TurnContext, effect: StatusEffect, name: &str) {
+    match ctx.effects.iter_mut().find(|x| x.target == effect.target && x.kind == effect.kind) {
+        Some(current) => {
+            current.potency = current.potency.max(effect.potency);
+            current.turns = current.turns.max(effect.turns);
+        }
+        None => ctx.effects.push(effect),
+    }
+
+    ctx.log.push(format!("{name} is {}", effect.kind.to_string().to_lowercase()));
+}
+
+/// Uses up a stun on `target`, returning whether there was one
+fn take_stun(ctx: &mut TurnContext, target: Combatant) -> bool {
+    match ctx.effects.iter().position(|x| x.target == target && x.kind == StatusKind::Stun) {
+        Some(i) => {
+            ctx.effects.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Lets a shield on `target` soak up what it can, returning the damage that gets through
+fn absorb(ctx: &mut TurnContext, target: Combatant, name: &str, damage: i32) -> i32 {
+    let index = match ctx.effects.iter().position(|x| x.target == target && x.kind == StatusKind::Shield) {
+        Some(i) => i,
+        None => return damage,
+    };
+
+    let shield = &mut ctx.effects[index];
+    let absorbed = shield.potency.min(damage);
+    shield.potency -= absorbed;
+    ctx.log.push(format!("{name}'s shield absorbs {absorbed} damage"));
+
+    if shield.potency == 0 {
+        ctx.effects.remove(index);
+        ctx.log.push(format!("{name}'s shield breaks"));
+    }
+
+    damage - absorbed
+}
+
+/// Poison and regeneration take effect, then every effect counts down a turn.
+/// Effects on anyone already out of the fight are dropped.
+fn tick_effects(members: &mut [Character], monsters: &mut [Monster], ctx: &mut TurnContext) {
+    let mut remaining = vec![];
+
+    for mut effect in std::mem::take(&mut ctx.effects) {
+        let (name, unit, is_monster) = match effect.target {
+            Combatant::Member(id) => match members.iter_mut().find(|x| x.id == id && x.unit.stats.health > 0) {
+                Some(x) => (&x.name, &mut x.unit, false),
+                None => continue,
+            },
+            Combatant::Monster(i) => match monsters.get_mut(i).filter(|x| x.is_active()) {
+                Some(x) => (&x.name, &mut x.unit, true),
+                None => continue,
+            },
+        };
+
+        match effect.kind {
+            StatusKind::Poison => {
+                unit.stats.health = (unit.stats.health - effect.potency).max(0);
+                ctx.log.push(format!("{name} takes {} poison damage", effect.potency));
+
+                if unit.stats.health == 0 {
+                    let outcome = if is_monster { "is defeated" } else { "is knocked out" };
+                    ctx.log.push(format!("{name} {outcome}"));
+                    continue;
+                }
+            }
+            StatusKind::Regen => {
+                unit.restore(Stats {
+                    health: effect.potency,
+                    ..Stats::default()
+                });
+                ctx.log.push(format!("{name} regenerates {} health", effect.potency));
+            }
+            StatusKind::Stun | StatusKind::Shield => {}
+        }
+
+        effect.turns = effect.turns.saturating_sub(1);
+        if effect.turns == 0 {
+            ctx.log.push(format!("{name} is no longer {}", effect.kind.to_string().to_lowercase()));
+        } else {
+            remaining.push(effect);
+        }
+    }
+
+    ctx.effects = remaining;
+}
+
+/// The party member with the character id `target`, if still standing
+fn ally_index(members: &[Character], target: usize) -> Option<usize> {
+    members.iter().position(|x| x.id as usize == target && x.unit.stats.health > 0)
+}
+
+/// The monster the party member aimed at, or the first one still standing if it's already down
+fn target_index(monsters: &[Monster], wanted: usize) -> Option<usize> {
+    match monsters.get(wanted) {
+        Some(x) if x.is_active() => Some(wanted),
+        _ => monsters.iter().position(|x| x.is_active()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bestiary::{self, MonsterFamily};
+    use crate::data::CharacterClass;
+
+    /// A warrior about to swing at a lone rat
+    fn fight(seed: u64) -> (Vec<Character>, Combat) {
+        let members = vec![Character::new(1, 1, "Al".to_string(), CharacterClass::Warrior)];
+        let combat = Combat {
+            monsters: vec![bestiary::regulars(MonsterFamily::Vermin)[0].spawn(1)],
+            pending: vec![TurnAction {
+                character_id: 1,
+                action: CombatAction::WeaponAttack,
+                target: 0,
+            }],
+            seed,
+            ..Combat::default()
+        };
+
+        (members, combat)
+    }
+
+    #[test]
+    fn a_turn_plays_out_the_same_from_the_same_seed() {
+        for seed in 0..20 {
+            let (mut members, mut combat) = fight(seed);
+            let (mut members_again, mut combat_again) = fight(seed);
+
+            resolve_turn(&mut members, &mut combat);
+            resolve_turn(&mut members_again, &mut combat_again);
+
+            assert_eq!(combat.turn, 1);
+            assert!(combat.pending.is_empty());
+            assert!(!combat.log.is_empty());
+            assert_eq!(serde_json::to_string(&combat).unwrap(), serde_json::to_string(&combat_again).unwrap());
+            assert_eq!(serde_json::to_string(&members).unwrap(), serde_json::to_string(&members_again).unwrap());
+        }
+    }
+
+    #[test]
+    fn a_member_who_chose_can_end_the_turn_for_the_rest() {
+        let (mut members, mut combat) = fight(1);
+        members.push(Character::new(2, 2, "Bo".to_string(), CharacterClass::Mage));
+
+        assert!(end_turn(&mut members, &mut combat, 2).is_err());
+        assert_eq!(combat.turn, 0);
+
+        end_turn(&mut members, &mut combat, 1).unwrap();
+        assert_eq!(combat.turn, 1);
+        assert!(combat.pending.is_empty());
+    }
+
+    #[test]
+    fn ally_skills_aim_at_a_character_id() {
+        let (mut members, mut combat) = fight(1);
+        members.insert(0, Character::new(5, 2, "Bo".to_string(), CharacterClass::Mage));
+        combat.pending.clear();
+        let defense = members[0].unit.stats.defense;
+
+        let shield_wall = |target| TurnAction {
+            character_id: 1,
+            action: CombatAction::UseSkill("Shield Wall".to_string()),
+            target,
+        };
+        assert!(submit_action(&mut members, &mut combat, shield_wall(0)).is_err());
+        submit_action(&mut members, &mut combat, shield_wall(5)).unwrap();
+        end_turn(&mut members, &mut combat, 1).unwrap();
+
+        assert!(members[0].unit.stats.defense > defense);
+    }
+}
@@ -1,35 +1,140 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::sync::{Arc, RwLock};
 
 use serde::{Deserialize, Serialize};
 
-use crate::quest_data::Quest;
+use crate::error::ApiError;
+use crate::inn;
+use crate::items;
+use crate::quest_data::{Quest, RewardPolicy};
+use crate::storage::{Storage, StorageError};
 
 pub const MAX_PARTY_SIZE: usize = 3;
 pub const MAX_COMBAT_ENEMIES: usize = 5;
 pub const MAX_ENCOUNTER_LENGTH: usize = 3;
 
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct ServerState {
-    pub users: [Option<User>; 100],
-    pub characters: [Option<Character>; 100],
-    pub quests: [Option<Quest>; 100],
+    pub users: Collection<User>,
+    pub passwords: Collection<String>,
+    pub sessions: HashMap<String, u32>,
+    pub characters: Collection<Character>,
+    pub quests: Collection<Quest>,
+    pub parties: Collection<Party>,
+    pub storage: Storage,
+    /// How new quests share their rewards
+    pub reward_policy: RewardPolicy,
+    /// Seconds it takes a resting character to recover a point of health and energy, 0 turns it off
+    pub regen_interval: u64,
+}
+
+/// Records keyed by a server-assigned id, optionally capped at `limit` entries
+#[derive(Clone, Debug)]
+pub struct Collection<T> {
+    name: &'static str,
+    records: BTreeMap<u32, T>,
+    next_id: u32,
+    limit: Option<usize>,
 }
 
 pub type SharedState = Arc<RwLock<ServerState>>;
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct User {
-    pub id: i32,
+    pub id: u32,
     pub username: String,
-    pub characters: [Character; 1],
+    /// Ids of the characters this user owns
+    #[serde(default)]
+    pub character_ids: Vec<u32>,
 }
 
-#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Session {
+    pub token: String,
+    pub user: User,
+}
+
+/// A group of up to `MAX_PARTY_SIZE` users going on quests together.
+/// Parties only live in memory, like the sessions of their members.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Party {
+    pub id: u32,
+    pub leader: u32,
+    pub members: Vec<PartyMember>,
+    /// Usernames invited to the party that haven't accepted yet
+    pub invited: Vec<String>,
+    /// The quest the party is currently on
+    pub quest_id: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PartyMember {
+    pub user_id: u32,
+    pub username: String,
+    pub character_id: u32,
+}
+
+/// The party the caller is in, and the parties inviting them
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PartyLobby {
+    pub party: Option<Party>,
+    pub invites: Vec<Party>,
+}
+
+/// What the client sends to create or accept a party
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct JoinParty {
+    pub character_id: u32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PartyInvite {
+    pub username: String,
+}
+
+/// What the client sends to create a character
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct NewCharacter {
+    pub name: String,
+    pub class: CharacterClass,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CharacterClass {
+    #[default]
+    Warrior,
+    Mage,
+    Rogue,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Character {
+    #[serde(default)]
+    pub id: u32,
+    #[serde(default)]
+    pub owner: u32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub class: CharacterClass,
     pub unit: Unit,
     pub experience: u32,
     pub coins: u32,
+    #[serde(default, alias = "items")]
+    pub inventory: Inventory,
+    #[serde(default)]
+    pub equipment: Equipment,
+    /// Unix time health and energy have been recovering from, see `inn`
+    #[serde(default)]
+    pub resting_since: Option<u64>,
 }
 
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
@@ -38,37 +143,455 @@ pub struct Unit {
     pub max_stats: Stats,
 }
 
-#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Stats {
     pub health: i32,
     pub energy: i32,
+    #[serde(default)]
+    pub attack: i32,
+    #[serde(default)]
+    pub defense: i32,
+    /// Faster units act earlier in a combat turn
+    #[serde(default)]
+    pub speed: i32,
+    /// Percent chance to deal double damage
+    #[serde(default)]
+    pub crit: i32,
+    /// Percent chance to dodge an attack
+    #[serde(default)]
+    pub evasion: i32,
 }
 
+/// A stack of items sharing a name. Items saved with just a name load as a single miscellaneous item.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Item {
     pub name: String,
+    pub kind: ItemKind,
+    pub quantity: u32,
+    /// For consumables the stats restored on use, for gear the bonus while equipped
+    pub effect: Stats,
+    pub rarity: Rarity,
 }
 
-impl Unit {
-    pub fn new_lvl_one() -> Unit {
-        Unit {
-            stats: Stats {
-                health: 10,
-                energy: 10,
+/// How hard an item is to come by
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Rarity {
+    #[default]
+    Common,
+    Uncommon,
+    Rare,
+    Epic,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ItemKind {
+    Consumable,
+    Weapon,
+    Armor,
+    Trinket,
+    #[default]
+    Misc,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EquipSlot {
+    Weapon,
+    Armor,
+    Trinket,
+}
+
+/// The gear a character has on, one item per slot
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Equipment {
+    pub weapon: Option<Item>,
+    pub armor: Option<Item>,
+    pub trinket: Option<Item>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Equip {
+    pub item: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Unequip {
+    pub slot: EquipSlot,
+}
+
+/// The items a character carries, identical items share a stack
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Inventory {
+    pub items: Vec<Item>,
+}
+
+impl ServerState {
+    /// Creates the state and fills it with everything previously saved in storage
+    pub fn new(storage: Storage, limit: Option<usize>) -> Result<ServerState, StorageError> {
+        let mut state = ServerState {
+            users: Collection::new("users", limit),
+            passwords: Collection::new("passwords", limit),
+            sessions: HashMap::new(),
+            characters: Collection::new("characters", limit),
+            quests: Collection::new("quests", limit),
+            parties: Collection::new("parties", limit),
+            storage,
+            reward_policy: RewardPolicy::default(),
+            regen_interval: inn::DEFAULT_REGEN_INTERVAL,
+        };
+
+        for (id, user) in state.storage.load_users()? {
+            state.users.insert(id, user);
+        }
+
+        for (id, hash) in state.storage.load_passwords()? {
+            state.passwords.insert(id, hash);
+        }
+
+        for (id, mut character) in state.storage.load_characters()? {
+            character.inventory.refresh();
+            character.resting_since.get_or_insert_with(inn::now);
+            state.characters.insert(id, character);
+        }
+
+        for (id, quest) in state.storage.load_quests()? {
+            state.quests.insert(id, quest);
+        }
+
+        Ok(state)
+    }
+
+    /// The character with `id`, as long as it belongs to `user`
+    pub fn owned_character(&self, user: u32, id: u32) -> Result<&Character, ApiError> {
+        match self.characters.get(id) {
+            Some(chr) if chr.owner == user => Ok(chr),
+            _ => Err(ApiError::NotFound(format!("character {id}"))),
+        }
+    }
+
+    /// Whether the character is out on a quest that isn't over yet
+    pub fn on_quest(&self, character_id: u32) -> bool {
+        self.quests
+            .values()
+            .any(|x| !x.status.is_finished() && x.members.iter().any(|m| m.id == character_id))
+    }
+
+    /// Saves the members of a finished quest over the stored characters, with everything
+    /// they earned, found and used up. Fallen members are dragged home with 1 health, and start resting.
+    pub fn return_from_quest(&mut self, quest: &Quest) -> Result<(), ApiError> {
+        for member in &quest.members {
+            if self.characters.get(member.id).is_none() {
+                continue;
+            }
+
+            let mut chr = member.clone();
+            chr.unit.stats.health = chr.unit.stats.health.max(1);
+            // A fight given up on halfway never got to end and wear off its buffs
+            chr.unit.reset_combat_stats();
+            chr.resting_since = Some(inn::now());
+
+            self.storage.save_character(chr.id, &chr)?;
+            self.characters.insert(chr.id, chr);
+        }
+
+        Ok(())
+    }
+
+    pub fn party_of(&self, user: u32) -> Option<&Party> {
+        self.parties
+            .values()
+            .find(|x| x.members.iter().any(|m| m.user_id == user))
+    }
+}
+
+impl<T> Collection<T> {
+    pub fn new(name: &'static str, limit: Option<usize>) -> Collection<T> {
+        Collection {
+            name,
+            records: BTreeMap::new(),
+            next_id: 1,
+            limit,
+        }
+    }
+
+    /// The id the next record should be stored under, or an error if the collection is full
+    pub fn vacant_id(&self) -> Result<u32, ApiError> {
+        match self.limit {
+            Some(limit) if self.records.len() >= limit => Err(ApiError::LimitReached(self.name.to_string())),
+            _ => Ok(self.next_id),
+        }
+    }
+
+    pub fn insert(&mut self, id: u32, value: T) {
+        self.records.insert(id, value);
+        self.next_id = self.next_id.max(id + 1);
+    }
+
+    pub fn get(&self, id: u32) -> Option<&T> {
+        self.records.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut T> {
+        self.records.get_mut(&id)
+    }
+
+    pub fn remove(&mut self, id: u32) -> Option<T> {
+        self.records.remove(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &T)> {
+        self.records.iter().map(|(id, value)| (*id, value))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.records.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+impl CharacterClass {
+    pub const ALL: [CharacterClass; 3] = [CharacterClass::Warrior, CharacterClass::Mage, CharacterClass::Rogue];
+
+    /// Max stats of a level one character of this class
+    pub fn base_stats(&self) -> Stats {
+        match self {
+            CharacterClass::Warrior => Stats {
+                health: 18,
+                energy: 12,
+                speed: 3,
+                crit: 5,
+                evasion: 5,
+                ..Stats::default()
             },
-            max_stats: Stats {
+            CharacterClass::Mage => Stats {
+                health: 12,
+                energy: 21,
+                speed: 2,
+                crit: 5,
+                evasion: 5,
+                ..Stats::default()
+            },
+            CharacterClass::Rogue => Stats {
                 health: 15,
                 energy: 15,
+                speed: 6,
+                crit: 15,
+                evasion: 15,
+                ..Stats::default()
             },
         }
     }
+
+    /// Max stats gained for every level past the first
+    pub fn growth(&self) -> Stats {
+        match self {
+            CharacterClass::Warrior => Stats { health: 4, energy: 1, ..Stats::default() },
+            CharacterClass::Mage => Stats { health: 1, energy: 4, ..Stats::default() },
+            CharacterClass::Rogue => Stats { health: 2, energy: 2, ..Stats::default() },
+        }
+    }
+
+    pub fn starting_items(&self) -> Vec<Item> {
+        let names = match self {
+            CharacterClass::Warrior => ["Rusty Sword", "Wooden Shield"],
+            CharacterClass::Mage => ["Gnarled Staff", "Tattered Spellbook"],
+            CharacterClass::Rogue => ["Twin Daggers", "Lockpicks"],
+        };
+
+        names
+            .iter()
+            .chain(["Cheese Wedge"].iter())
+            .map(|name| items::item(name))
+            .collect()
+    }
+}
+
+impl fmt::Display for CharacterClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CharacterClass::Warrior => write!(f, "Warrior"),
+            CharacterClass::Mage => write!(f, "Mage"),
+            CharacterClass::Rogue => write!(f, "Rogue"),
+        }
+    }
+}
+
+impl Unit {
+    /// A level one unit of `class`, starting out a bit worn down from the road
+    pub fn new_lvl_one(class: CharacterClass) -> Unit {
+        let max_stats = class.base_stats();
+
+        Unit {
+            stats: Stats {
+                health: max_stats.health * 2 / 3,
+                energy: max_stats.energy * 2 / 3,
+                ..max_stats
+            },
+            max_stats,
+        }
+    }
+
+    /// Drops any combat stats raised or lowered during a fight back to their maximum
+    pub fn reset_combat_stats(&mut self) {
+        self.stats = Stats {
+            health: self.stats.health,
+            energy: self.stats.energy,
+            ..self.max_stats
+        };
+    }
+
+    /// Applies `effect` to the current stats without going over the maximum
+    pub fn restore(&mut self, effect: Stats) {
+        self.stats.health = (self.stats.health + effect.health).clamp(0, self.max_stats.health);
+        self.stats.energy = (self.stats.energy + effect.energy).clamp(0, self.max_stats.energy);
+    }
 }
 
 impl Character {
-    pub fn new() -> Character {
-        Character {
-            unit: Unit::new_lvl_one(),
+    /// A fresh level one character, wearing the gear of its class
+    pub fn new(id: u32, owner: u32, name: String, class: CharacterClass) -> Character {
+        let mut chr = Character {
+            id,
+            owner,
+            name,
+            class,
+            unit: Unit::new_lvl_one(class),
             experience: 0,
             coins: 0,
+            inventory: Inventory {
+                items: class.starting_items(),
+            },
+            equipment: Equipment::default(),
+            resting_since: None,
+        };
+
+        chr.equip_starting_gear();
+        chr
+    }
+}
+
+impl Default for Item {
+    fn default() -> Item {
+        Item {
+            name: String::new(),
+            kind: ItemKind::default(),
+            quantity: 1,
+            effect: Stats::default(),
+            rarity: Rarity::default(),
+        }
+    }
+}
+
+impl Item {
+    /// Brings the item up to date with its definition in `items::ITEMS`, keeping the quantity
+    pub fn refresh(&mut self) {
+        if let Some(template) = items::find(&self.name) {
+            *self = template.build(self.quantity);
+        }
+    }
+}
+
+impl Inventory {
+    pub fn get(&self, name: &str) -> Option<&Item> {
+        self.items.iter().find(|x| x.name == name)
+    }
+
+    /// Adds `item` to the stack of the same name, or starts a new one
+    pub fn add(&mut self, item: Item) {
+        match self.items.iter_mut().find(|x| x.name == item.name) {
+            Some(stack) => stack.quantity += item.quantity,
+            None => self.items.push(item),
         }
     }
+
+    /// Brings every item up to date with its definition in `items::ITEMS`, keeping the quantities
+    pub fn refresh(&mut self) {
+        for item in self.items.iter_mut() {
+            item.refresh();
+        }
+    }
+
+    /// Takes a single `name` out of the inventory, dropping the stack once it's empty
+    pub fn take(&mut self, name: &str) -> Option<Item> {
+        let index = self.items.iter().position(|x| x.name == name)?;
+        let stack = &mut self.items[index];
+        stack.quantity = stack.quantity.saturating_sub(1);
+
+        let item = Item {
+            quantity: 1,
+            ..stack.clone()
+        };
+
+        if stack.quantity == 0 {
+            self.items.remove(index);
+        }
+
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bestiary::{self, MonsterFamily};
+    use crate::combat;
+    use crate::quest_data::{Combat, CombatAction, Encounter, QuestStatus, TurnAction};
+
+    #[test]
+    fn buffs_wear_off_when_a_quest_is_abandoned_mid_fight() {
+        let mut state = ServerState::new(Storage::open_in_memory().unwrap(), None).unwrap();
+        let chr = Character::new(1, 1, "Al".to_string(), CharacterClass::Warrior);
+        state.characters.insert(chr.id, chr.clone());
+
+        let mut members = vec![chr];
+        let mut combat = Combat {
+            monsters: vec![bestiary::regulars(MonsterFamily::Vermin)[0].spawn(1)],
+            ..Combat::default()
+        };
+        let shield_wall = TurnAction {
+            character_id: 1,
+            action: CombatAction::UseSkill("Shield Wall".to_string()),
+            target: 1,
+        };
+        combat::submit_action(&mut members, &mut combat, shield_wall).unwrap();
+
+        let unit = &members[0].unit;
+        assert!(unit.stats.defense > unit.max_stats.defense);
+
+        let mut quest = Quest {
+            status: QuestStatus::InProgress,
+            members,
+            open_encounter: Some(Encounter::CombatEncounter(combat)),
+            ..Quest::default()
+        };
+        quest.abandon().unwrap();
+        state.return_from_quest(&quest).unwrap();
+
+        let unit = &state.characters.get(1).unwrap().unit;
+        assert_eq!(unit.stats.defense, unit.max_stats.defense);
+    }
+
+    #[test]
+    fn characters_saved_before_the_combat_stats_still_load() {
+        let saved = r#"{"unit":{"stats":{"health":7,"energy":3},"max_stats":{"health":10,"energy":5}},"experience":40,"coins":12}"#;
+        let chr: Character = serde_json::from_str(saved).unwrap();
+
+        let stats = Stats { health: 7, energy: 3, ..Stats::default() };
+        let max_stats = Stats { health: 10, energy: 5, ..Stats::default() };
+        assert_eq!(chr.unit.stats, stats);
+        assert_eq!(chr.unit.max_stats, max_stats);
+        assert_eq!((chr.experience, chr.coins), (40, 12));
+        assert_eq!(chr.class, CharacterClass::default());
+        assert!(chr.inventory.items.is_empty());
+        assert!(chr.resting_since.is_none());
+    }
 }
@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bestiary::MonsterFamily;
+use crate::game_data::{self, Authored};
+use crate::items;
+use crate::quest_data::EncounterReward;
+
+/// A conversation with a stranger as written in `data/dialogues.json`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DialogueDefinition {
+    pub id: String,
+    /// Who the party is talking to
+    pub npc: String,
+    /// The node the conversation opens with
+    pub start: String,
+    pub nodes: Vec<DialogueNode>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DialogueNode {
+    pub id: String,
+    /// What the stranger says
+    pub text: String,
+    pub choices: Vec<ChoiceDefinition>,
+}
+
+/// A response the party can give, and where it leads
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChoiceDefinition {
+    pub text: String,
+    pub outcome: DialogueOutcome,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum DialogueOutcome {
+    /// Carries on with another node of the same dialogue
+    Goto(String),
+    /// Ends the conversation with a reward, scaled by the party level
+    Reward(EncounterReward),
+    /// Ends the conversation with the member who chose it paying `price` coins per party level for `item`
+    Trade { price: u32, item: String },
+    /// The stranger turns on the party, or calls in monsters of the family
+    Fight(MonsterFamily),
+    /// Ends the conversation with nothing gained
+    Leave,
+}
+
+static DIALOGUES: LazyLock<Result<Vec<DialogueDefinition>, String>> = LazyLock::new(|| game_data::load(include_str!("../data/dialogues.json")));
+
+pub fn all() -> &'static [DialogueDefinition] {
+    game_data::all(&DIALOGUES)
+}
+
+pub fn find(id: &str) -> Option<&'static DialogueDefinition> {
+    all().iter().find(|x| x.id == id)
+}
+
+/// Checks the dialogues in `data/dialogues.json` can all be played through
+pub fn validate() -> Result<(), Vec<String>> {
+    game_data::validate(&DIALOGUES)
+}
+
+impl DialogueDefinition {
+    pub fn node(&self, id: &str) -> Option<&DialogueNode> {
+        self.nodes.iter().find(|x| x.id == id)
+    }
+
+}
+
+impl Authored for DialogueDefinition {
+    const KIND: &'static str = "dialogue";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Item rewards are written with just a name, the rest comes from the item list
+    fn complete(&mut self) {
+        for choice in self.nodes.iter_mut().flat_map(|x| x.choices.iter_mut()) {
+            if let DialogueOutcome::Reward(EncounterReward::ItemReward(item)) = &mut choice.outcome {
+                item.refresh();
+            }
+        }
+    }
+
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut ids = HashSet::new();
+
+        if self.node(&self.start).is_none() {
+            problems.push(format!("start node {} doesn't exist", self.start));
+        }
+
+        for node in &self.nodes {
+            if !ids.insert(&node.id) {
+                problems.push(format!("node {} is defined twice", node.id));
+            }
+            if node.choices.is_empty() {
+                problems.push(format!("node {} has no choices", node.id));
+            }
+
+            for choice in &node.choices {
+                match &choice.outcome {
+                    DialogueOutcome::Goto(next) if self.node(next).is_none() => {
+                        problems.push(format!("node {} leads to {next}, which doesn't exist", node.id));
+                    }
+                    DialogueOutcome::Trade { item, .. } if items::find(item).is_none() => {
+                        problems.push(format!("node {} trades {item}, which isn't an item", node.id));
+                    }
+                    DialogueOutcome::Reward(EncounterReward::ItemReward(item)) if items::find(&item.name).is_none() => {
+                        problems.push(format!("node {} gives {}, which isn't an item", node.id, item.name));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        problems
+    }
+}
@@ -0,0 +1,114 @@
+use crate::data::{Character, EquipSlot, Equipment, Item, ItemKind, Stats, Unit};
+use crate::error::ApiError;
+
+impl ItemKind {
+    /// The slot gear of this kind goes into, `None` for things that can't be worn
+    pub fn slot(self) -> Option<EquipSlot> {
+        match self {
+            ItemKind::Weapon => Some(EquipSlot::Weapon),
+            ItemKind::Armor => Some(EquipSlot::Armor),
+            ItemKind::Trinket => Some(EquipSlot::Trinket),
+            ItemKind::Consumable | ItemKind::Misc => None,
+        }
+    }
+}
+
+impl Equipment {
+    pub fn slot(&self, slot: EquipSlot) -> Option<&Item> {
+        match slot {
+            EquipSlot::Weapon => self.weapon.as_ref(),
+            EquipSlot::Armor => self.armor.as_ref(),
+            EquipSlot::Trinket => self.trinket.as_ref(),
+        }
+    }
+
+    fn slot_mut(&mut self, slot: EquipSlot) -> &mut Option<Item> {
+        match slot {
+            EquipSlot::Weapon => &mut self.weapon,
+            EquipSlot::Armor => &mut self.armor,
+            EquipSlot::Trinket => &mut self.trinket,
+        }
+    }
+}
+
+impl Unit {
+    /// Adds the bonus of a piece of gear. Health and energy only grow their maximum.
+    fn wear(&mut self, bonus: Stats) {
+        self.max_stats.health += bonus.health;
+        self.max_stats.energy += bonus.energy;
+        add_combat_stats(&mut self.max_stats, bonus, 1);
+        add_combat_stats(&mut self.stats, bonus, 1);
+    }
+
+    /// Takes the bonus of a piece of gear away again, current stats can't stay above the new maximum
+    fn take_off(&mut self, bonus: Stats) {
+        self.max_stats.health -= bonus.health;
+        self.max_stats.energy -= bonus.energy;
+        add_combat_stats(&mut self.max_stats, bonus, -1);
+        add_combat_stats(&mut self.stats, bonus, -1);
+        self.restore(Stats::default());
+    }
+}
+
+/// Adds `sign` times every stat of `bonus` other than health and energy
+fn add_combat_stats(stats: &mut Stats, bonus: Stats, sign: i32) {
+    stats.attack += bonus.attack * sign;
+    stats.defense += bonus.defense * sign;
+    stats.speed += bonus.speed * sign;
+    stats.crit += bonus.crit * sign;
+    stats.evasion += bonus.evasion * sign;
+}
+
+impl Character {
+    /// Moves `name` from the inventory into its slot, putting back whatever was worn there
+    pub fn equip(&mut self, name: &str) -> Result<(), ApiError> {
+        let slot = match self.inventory.get(name) {
+            Some(item) => item
+                .kind
+                .slot()
+                .ok_or(ApiError::BadRequest(format!("{name} can't be equipped")))?,
+            None => return Err(ApiError::NotFound(format!("{name} in {}'s inventory", self.name))),
+        };
+
+        if self.equipment.slot(slot).is_some() {
+            self.unequip(slot)?;
+        }
+
+        if let Some(item) = self.inventory.take(name) {
+            self.unit.wear(item.effect);
+            *self.equipment.slot_mut(slot) = Some(item);
+        }
+
+        Ok(())
+    }
+
+    /// Moves the item worn in `slot` back into the inventory
+    pub fn unequip(&mut self, slot: EquipSlot) -> Result<(), ApiError> {
+        let item = self
+            .equipment
+            .slot_mut(slot)
+            .take()
+            .ok_or(ApiError::BadRequest(format!("{} has nothing equipped as {slot:?}", self.name)))?;
+
+        self.unit.take_off(item.effect);
+        self.inventory.add(item);
+
+        Ok(())
+    }
+
+    /// Puts on the first carried item for every empty slot
+    pub fn equip_starting_gear(&mut self) {
+        let gear: Vec<(String, EquipSlot)> = self
+            .inventory
+            .items
+            .iter()
+            .filter_map(|x| x.kind.slot().map(|slot| (x.name.clone(), slot)))
+            .collect();
+
+        for (name, slot) in gear {
+            if self.equipment.slot(slot).is_none() {
+                self.equip(&name).expect("gear just found in the inventory should be equippable");
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt;
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::storage::StorageError;
+
+/// Error body returned by the api, shared with the client so it can be deserialized again
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ApiError {
+    Storage(String),
+    LimitReached(String),
+    UsernameTaken(String),
+    InvalidCredentials,
+    Unauthorized,
+    Forbidden(String),
+    NotFound(String),
+    BadRequest(String),
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::LimitReached(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::UsernameTaken(_) => StatusCode::CONFLICT,
+            ApiError::InvalidCredentials | ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Storage(e) => write!(f, "Storage failure: {e}"),
+            ApiError::LimitReached(collection) => write!(f, "The server can't hold any more {collection}"),
+            ApiError::UsernameTaken(name) => write!(f, "The username {name} is already taken"),
+            ApiError::InvalidCredentials => write!(f, "Wrong username or password"),
+            ApiError::Unauthorized => write!(f, "You need to log in first"),
+            ApiError::Forbidden(e) => write!(f, "{e}"),
+            ApiError::NotFound(what) => write!(f, "Couldn't find {what}"),
+            ApiError::BadRequest(e) => write!(f, "{e}"),
+            ApiError::Internal(e) => write!(f, "Internal server error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<StorageError> for ApiError {
+    fn from(e: StorageError) -> Self {
+        ApiError::Storage(e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    /// Storage failures are logged here, the client only learns that its changes weren't saved
+    fn into_response(self) -> Response {
+        let body = match self {
+            ApiError::Storage(e) => {
+                eprintln!("Storage error: {e}");
+                ApiError::Storage("your changes couldn't be saved, try again later".to_string())
+            }
+            other => other,
+        };
+
+        (body.status(), Json(body)).into_response()
+    }
+}
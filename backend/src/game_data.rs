@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+
+use serde::de::DeserializeOwned;
+
+/// One entry of a game data file in `data/`, like a riddle or a dialogue
+pub trait Authored: DeserializeOwned {
+    /// What an entry is called in problems, like "riddle"
+    const KIND: &'static str;
+
+    fn id(&self) -> &str;
+
+    /// Fills in whatever the file leaves out
+    fn complete(&mut self) {}
+
+    /// What's wrong with the entry on its own
+    fn problems(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Parses the entries written in `data`, a JSON list of them
+pub fn load<T: Authored>(data: &str) -> Result<Vec<T>, String> {
+    let mut entries: Vec<T> = serde_json::from_str(data).map_err(|e| format!("not a list of {}s: {e}", T::KIND))?;
+    entries.iter_mut().for_each(T::complete);
+
+    Ok(entries)
+}
+
+/// Every entry of a loaded file, none if it couldn't be read. `validate` tells why at start.
+pub fn all<T>(file: &'static Result<Vec<T>, String>) -> &'static [T] {
+    file.as_deref().unwrap_or_default()
+}
+
+/// Checks a loaded file has entries, each with its own id and none of them broken,
+/// so a typo shows up at start rather than halfway into a quest. Returns every problem found.
+pub fn validate<T: Authored>(file: &Result<Vec<T>, String>) -> Result<(), Vec<String>> {
+    let entries = file.as_ref().map_err(|e| vec![e.clone()])?;
+    let mut problems = Vec::new();
+    let mut ids = HashSet::new();
+
+    if entries.is_empty() {
+        problems.push(format!("there should be at least one {}", T::KIND));
+    }
+
+    for entry in entries {
+        if !ids.insert(entry.id()) {
+            problems.push(format!("{} {} is defined twice", T::KIND, entry.id()));
+        }
+        problems.extend(entry.problems().into_iter().map(|x| format!("{} {}: {x}", T::KIND, entry.id())));
+    }
+
+    if problems.is_empty() { Ok(()) } else { Err(problems) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialogues;
+    use crate::riddles::{self, RiddleDefinition};
+
+    #[test]
+    fn the_shipped_data_is_valid() {
+        assert_eq!(riddles::validate(), Ok(()));
+        assert_eq!(dialogues::validate(), Ok(()));
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let data = r#"[
+            {"id": "moon", "question": "?", "answers": [], "attempts": 0},
+            {"id": "moon", "question": "?", "answers": ["moon"], "attempts": 1}
+        ]"#;
+
+        let problems = validate(&load::<RiddleDefinition>(data)).unwrap_err();
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        assert!(validate(&load::<RiddleDefinition>("{}")).is_err());
+    }
+}
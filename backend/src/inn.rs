@@ -0,0 +1,168 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{Extension, Json, Router, routing::post};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::AuthUser;
+use crate::data::{Character, ServerState, SharedState, Stats};
+use crate::error::ApiError;
+
+/// What a night at the inn costs per character level
+pub const FEE_PER_LEVEL: u32 = 5;
+/// Resting characters recover a point of health and energy every ten minutes unless configured otherwise
+pub const DEFAULT_REGEN_INTERVAL: u64 = 10 * 60;
+
+/// What the client sends to rest one of their characters at the inn
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct InnStay {
+    pub character_id: u32,
+}
+
+pub fn routes() -> Router {
+    Router::new().route("/inn/rest", post(rest))
+}
+
+/// What resting at the inn costs `chr`
+pub fn fee(chr: &Character) -> u32 {
+    FEE_PER_LEVEL * chr.level()
+}
+
+/// Seconds since the unix epoch
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs())
+}
+
+impl Character {
+    /// Whether health and energy are both at their maximum
+    pub fn is_rested(&self) -> bool {
+        self.unit.stats.health >= self.unit.max_stats.health && self.unit.stats.energy >= self.unit.max_stats.energy
+    }
+
+    /// Recovers a point of health and energy for every `interval` seconds spent resting up to `now`.
+    /// Time left over counts toward the next point, an `interval` of 0 recovers nothing.
+    pub fn regenerate(&mut self, now: u64, interval: u64) {
+        let since = *self.resting_since.get_or_insert(now);
+        if interval == 0 {
+            return;
+        }
+
+        let points = now.saturating_sub(since) / interval;
+        if points == 0 {
+            return;
+        }
+
+        // No point can be recovered past the larger maximum, so that's as far as it needs counting
+        let cap = self.unit.max_stats.health.max(self.unit.max_stats.energy).max(0);
+        let recovered = i32::try_from(points).unwrap_or(i32::MAX).min(cap);
+        self.unit.restore(Stats {
+            health: recovered,
+            energy: recovered,
+            ..Stats::default()
+        });
+        self.resting_since = Some(since + points * interval);
+    }
+
+    /// Pays the inn to recover all health and energy, as long as the character can afford it
+    pub fn rest(&mut self, now: u64) -> Result<(), ApiError> {
+        if self.is_rested() {
+            return Err(ApiError::BadRequest(format!("{} is already well rested", self.name)));
+        }
+
+        let fee = fee(self);
+        if fee > self.coins {
+            return Err(ApiError::BadRequest(format!(
+                "A room costs {fee} coins, {} only has {}",
+                self.name, self.coins
+            )));
+        }
+
+        self.coins -= fee;
+        self.unit.restore(self.unit.max_stats);
+        self.resting_since = Some(now);
+
+        Ok(())
+    }
+}
+
+impl ServerState {
+    /// A copy of the character with `id` belonging to `user`, with what it recovered while resting
+    pub fn rested_character(&self, user: u32, id: u32) -> Result<Character, ApiError> {
+        let mut chr = self.owned_character(user, id)?.clone();
+        self.regenerate(&mut chr);
+        Ok(chr)
+    }
+
+    /// Recovers what `chr` regenerated since it last rested, unless the character is out on a quest
+    pub fn regenerate(&self, chr: &mut Character) {
+        if !self.on_quest(chr.id) {
+            chr.regenerate(now(), self.regen_interval);
+        }
+    }
+}
+
+/// Rests one of the caller's characters at the inn, paying from its coins
+async fn rest(Extension(state): Extension<SharedState>, user: AuthUser, Json(stay): Json<InnStay>) -> Result<Json<Character>, ApiError> {
+    let mut state = state.write().unwrap();
+    let id = stay.character_id;
+
+    let mut chr = state.rested_character(user.id, id)?;
+    if state.on_quest(id) {
+        return Err(ApiError::BadRequest(format!("{} is away on a quest", chr.name)));
+    }
+
+    chr.rest(now())?;
+
+    state.storage.save_character(id, &chr)?;
+    state.characters.insert(id, chr.clone());
+
+    Ok(Json(chr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::CharacterClass;
+
+    /// A character worn down to 1 health and energy, resting since `since`
+    fn tired(since: u64) -> Character {
+        let mut chr = Character::new(1, 1, "Al".to_string(), CharacterClass::Warrior);
+        chr.unit.stats.health = 1;
+        chr.unit.stats.energy = 1;
+        chr.resting_since = Some(since);
+        chr
+    }
+
+    #[test]
+    fn a_point_is_recovered_every_interval() {
+        let mut chr = tired(1000);
+
+        chr.regenerate(1000 + 3 * 60, 60);
+
+        assert_eq!((chr.unit.stats.health, chr.unit.stats.energy), (4, 4));
+        assert_eq!(chr.resting_since, Some(1000 + 3 * 60));
+    }
+
+    #[test]
+    fn time_short_of_a_point_carries_over() {
+        let mut chr = tired(1000);
+
+        chr.regenerate(1000 + 90, 60);
+        assert_eq!(chr.unit.stats.health, 2);
+        assert_eq!(chr.resting_since, Some(1000 + 60));
+
+        chr.regenerate(1000 + 120, 60);
+        assert_eq!(chr.unit.stats.health, 3);
+        assert_eq!(chr.resting_since, Some(1000 + 120));
+    }
+
+    #[test]
+    fn recovery_stops_at_the_maximum() {
+        let mut chr = tired(0);
+
+        chr.regenerate(u64::MAX, 1);
+
+        assert_eq!(chr.unit.stats.health, chr.unit.max_stats.health);
+        assert_eq!(chr.unit.stats.energy, chr.unit.max_stats.energy);
+        assert!(chr.is_rested());
+    }
+}
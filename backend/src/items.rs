@@ -0,0 +1,101 @@
+use crate::data::{Item, ItemKind, Rarity, Stats};
+
+/// The definition every item of a given name is built from
+pub struct ItemTemplate {
+    pub name: &'static str,
+    pub kind: ItemKind,
+    pub effect: Stats,
+    pub rarity: Rarity,
+}
+
+const fn consumable(name: &'static str, health: i32, energy: i32) -> ItemTemplate {
+    ItemTemplate {
+        name,
+        kind: ItemKind::Consumable,
+        effect: Stats {
+            health,
+            energy,
+            attack: 0,
+            defense: 0,
+            speed: 0,
+            crit: 0,
+            evasion: 0,
+        },
+        rarity: Rarity::Common,
+    }
+}
+
+/// Gear raises the max health and energy, attack and defense of whoever wears it
+const fn gear(name: &'static str, kind: ItemKind, attack: i32, defense: i32, health: i32, energy: i32) -> ItemTemplate {
+    ItemTemplate {
+        name,
+        kind,
+        effect: Stats {
+            health,
+            energy,
+            attack,
+            defense,
+            speed: 0,
+            crit: 0,
+            evasion: 0,
+        },
+        rarity: Rarity::Common,
+    }
+}
+
+const fn misc(name: &'static str) -> ItemTemplate {
+    gear(name, ItemKind::Misc, 0, 0, 0, 0)
+}
+
+pub const ITEMS: &[ItemTemplate] = &[
+    consumable("Cheese Rind", 3, 0),
+    consumable("Cheese Wedge", 6, 0),
+    consumable("Cheese Wheel", 14, 2).rarity(Rarity::Uncommon),
+    consumable("Herb Bundle", 2, 5),
+    consumable("Royal Jelly", 8, 8).rarity(Rarity::Rare),
+    gear("Rusty Sword", ItemKind::Weapon, 2, 0, 0, 0),
+    gear("Gnarled Staff", ItemKind::Weapon, 1, 0, 0, 3),
+    gear("Twin Daggers", ItemKind::Weapon, 2, 0, 0, 0),
+    gear("Wooden Shield", ItemKind::Armor, 0, 1, 2, 0),
+    gear("Tattered Spellbook", ItemKind::Trinket, 0, 0, 0, 2),
+    gear("Crown of Tails", ItemKind::Trinket, 0, 0, 3, 3).rarity(Rarity::Epic),
+    gear("Tomcat Collar", ItemKind::Trinket, 1, 1, 0, 0).rarity(Rarity::Rare),
+    gear("Warlord's Banner", ItemKind::Trinket, 2, 0, 0, 0).rarity(Rarity::Epic),
+    // Everything else is only good for selling
+    misc("Lockpicks"),
+    misc("Cat Whisker"),
+    misc("Ball of Yarn").rarity(Rarity::Uncommon),
+    misc("Chitin Shard"),
+    misc("Ferret Fang"),
+];
+
+pub fn find(name: &str) -> Option<&'static ItemTemplate> {
+    ITEMS.iter().find(|x| x.name == name)
+}
+
+/// A single item called `name`, items missing from `ITEMS` are plain miscellany
+pub fn item(name: &str) -> Item {
+    match find(name) {
+        Some(template) => template.build(1),
+        None => Item {
+            name: name.to_string(),
+            ..Item::default()
+        },
+    }
+}
+
+impl ItemTemplate {
+    const fn rarity(self, rarity: Rarity) -> ItemTemplate {
+        ItemTemplate { rarity, ..self }
+    }
+
+    pub fn build(&self, quantity: u32) -> Item {
+        Item {
+            name: self.name.to_string(),
+            kind: self.kind,
+            quantity,
+            effect: self.effect,
+            rarity: self.rarity,
+        }
+    }
+}
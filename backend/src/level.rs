@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+use crate::data::Character;
+
+pub const MAX_LEVEL: u32 = 20;
+const EXPERIENCE_STEP: u32 = 50;
+
+/// Reported in quest results whenever a member reaches a new level
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LevelUp {
+    pub character_id: u32,
+    pub name: String,
+    pub level: u32,
+}
+
+/// Total experience needed to reach `level`, each level costs 50 more than the last
+pub fn experience_for_level(level: u32) -> u32 {
+    let level = level.clamp(1, MAX_LEVEL);
+    EXPERIENCE_STEP * level * (level - 1) / 2
+}
+
+pub fn level_for_experience(experience: u32) -> u32 {
+    (1..MAX_LEVEL)
+        .take_while(|level| experience >= experience_for_level(level + 1))
+        .count() as u32
+        + 1
+}
+
+impl Character {
+    pub fn level(&self) -> u32 {
+        level_for_experience(self.experience)
+    }
+
+    /// Experience earned into the current level and the amount the next level needs,
+    /// or `None` at the max level
+    pub fn level_progress(&self) -> Option<(u32, u32)> {
+        let level = self.level();
+        if level >= MAX_LEVEL {
+            return None;
+        }
+
+        let floor = experience_for_level(level);
+        Some((self.experience - floor, experience_for_level(level + 1) - floor))
+    }
+
+    /// Adds experience and grows the max stats for every level gained.
+    /// The freshly gained stats are granted right away.
+    pub fn gain_experience(&mut self, amount: u32) -> Option<LevelUp> {
+        let before = self.level();
+        self.experience = self.experience.saturating_add(amount);
+        let after = self.level();
+
+        if after == before {
+            return None;
+        }
+
+        let growth = self.class.growth();
+        let levels = (after - before) as i32;
+
+        self.unit.max_stats.health += growth.health * levels;
+        self.unit.max_stats.energy += growth.energy * levels;
+        self.unit.stats.health += growth.health * levels;
+        self.unit.stats.energy += growth.energy * levels;
+
+        Some(LevelUp {
+            character_id: self.id,
+            name: self.name.clone(),
+            level: after,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::CharacterClass;
+
+    #[test]
+    fn each_level_costs_more_than_the_last() {
+        assert_eq!(experience_for_level(1), 0);
+        assert_eq!(experience_for_level(2), 50);
+        assert_eq!(experience_for_level(3), 150);
+        assert_eq!(experience_for_level(4), 300);
+
+        assert_eq!(level_for_experience(0), 1);
+        assert_eq!(level_for_experience(49), 1);
+        assert_eq!(level_for_experience(50), 2);
+        assert_eq!(level_for_experience(149), 2);
+        assert_eq!(level_for_experience(150), 3);
+    }
+
+    #[test]
+    fn levels_stop_at_the_max() {
+        assert_eq!(experience_for_level(MAX_LEVEL + 5), experience_for_level(MAX_LEVEL));
+        assert_eq!(level_for_experience(experience_for_level(MAX_LEVEL)), MAX_LEVEL);
+        assert_eq!(level_for_experience(u32::MAX), MAX_LEVEL);
+
+        let mut chr = Character::new(1, 1, "Al".to_string(), CharacterClass::Warrior);
+        let level_up = chr.gain_experience(u32::MAX).unwrap();
+        assert_eq!(level_up.level, MAX_LEVEL);
+        assert_eq!(chr.level_progress(), None);
+        assert_eq!(chr.gain_experience(1), None);
+    }
+}
@@ -1,3 +1,22 @@
+pub mod auth;
+pub mod bestiary;
+pub mod combat;
 pub mod data;
+pub mod dialogues;
+pub mod equipment;
+pub mod error;
+pub mod game_data;
+pub mod inn;
+pub mod items;
+pub mod level;
+pub mod loot;
+pub mod monster_ai;
+pub mod party;
 pub mod quest;
-pub mod quest_data;
\ No newline at end of file
+pub mod quest_data;
+pub mod quest_gen;
+pub mod riddles;
+pub mod shop;
+pub mod skills;
+pub mod status;
+pub mod storage;
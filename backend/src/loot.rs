@@ -0,0 +1,176 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::data::Item;
+use crate::error::ApiError;
+use crate::quest_data::{Combat, LootDrop, LootRoll, LootStatus, Quest, QuestStatus, RewardPolicy, RollChoice};
+
+/// Loot is rolled on its own stream of the fight's seed, so it doesn't repeat the rolls of the turns
+const LOOT_STREAM: u64 = 1;
+
+impl Combat {
+    /// What the monsters that didn't run off drop, every entry of their loot tables is rolled on its own
+    pub fn roll_loot(&self) -> Vec<Item> {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_stream(LOOT_STREAM);
+
+        self.monsters
+            .iter()
+            .filter(|x| !x.fled)
+            .flat_map(|x| x.loot.iter())
+            .filter(|x| rng.random_range(0..100) < x.chance)
+            .map(|x| x.item.clone())
+            .collect()
+    }
+}
+
+impl Quest {
+    /// Hands out items the party found. With the `Duplicate` policy every member gets a copy,
+    /// otherwise they go into the loot pool to be rolled for. A party of one takes everything.
+    pub fn hand_out(&mut self, items: Vec<Item>) {
+        for item in items {
+            match (self.reward_policy, self.members.as_mut_slice()) {
+                (_, [member]) => member.inventory.add(item),
+                (RewardPolicy::Duplicate, members) => {
+                    for member in members {
+                        member.inventory.add(item.clone());
+                    }
+                }
+                (RewardPolicy::Split, _) => self.loot.push(LootDrop {
+                    item,
+                    ..LootDrop::default()
+                }),
+            }
+        }
+    }
+
+    /// Rolls for the drop at `index` of the loot pool, the drop is handed out once every member rolled
+    pub fn roll_for_loot(&mut self, character_id: u32, index: usize, choice: RollChoice) -> Result<(), ApiError> {
+        if self.status != QuestStatus::InProgress {
+            return Err(ApiError::BadRequest("The loot has already been shared out".to_string()));
+        }
+
+        let drop = self.loot.get_mut(index).ok_or(ApiError::NotFound(format!("drop {index}")))?;
+
+        if drop.status != LootStatus::Rolling {
+            return Err(ApiError::BadRequest(format!("{} has already been handed out", drop.item.name)));
+        }
+        if drop.rolls.iter().any(|x| x.character_id == character_id) {
+            return Err(ApiError::BadRequest(format!("You already rolled for {}", drop.item.name)));
+        }
+
+        // Seeded like everything else on the quest, so a replayed quest rolls the same
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed.wrapping_add(index as u64));
+        rng.set_stream(character_id as u64);
+
+        let roll = match choice {
+            RollChoice::Pass => 0,
+            _ => rng.random_range(1..=100),
+        };
+        drop.rolls.push(LootRoll { character_id, choice, roll });
+
+        if drop.rolls.len() >= self.members.len() {
+            self.settle_drop(index);
+        }
+
+        // The last encounter is behind the party, so the quest ends with the last drop
+        if let Some(status) = self.ending
+            && !self.loot_pending()
+        {
+            self.finish(status)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether any drop in the loot pool is still being rolled for
+    pub fn loot_pending(&self) -> bool {
+        self.loot.iter().any(|x| x.status == LootStatus::Rolling)
+    }
+
+    /// Hands out every drop still being rolled for, members who didn't roll pass on it.
+    /// Only for a quest that is given up on, otherwise every member gets to roll.
+    pub fn settle_loot(&mut self) {
+        for index in 0..self.loot.len() {
+            if self.loot[index].status == LootStatus::Rolling {
+                self.settle_drop(index);
+            }
+        }
+    }
+
+    /// Gives the drop to the best roll, need before greed, the earlier roll winning a tie
+    fn settle_drop(&mut self, index: usize) {
+        let drop = &mut self.loot[index];
+
+        let winner = drop
+            .rolls
+            .iter()
+            .filter(|x| x.choice != RollChoice::Pass)
+            .rev()
+            .max_by_key(|x| (x.choice == RollChoice::Need, x.roll))
+            .map(|x| x.character_id);
+
+        drop.status = match winner {
+            Some(id) => LootStatus::Won(id),
+            None => LootStatus::Passed,
+        };
+
+        if let Some(member) = winner.and_then(|id| self.members.iter_mut().find(|x| x.id == id)) {
+            member.inventory.add(drop.item.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bestiary::{self, MonsterFamily};
+    use crate::data::{Character, CharacterClass};
+    use crate::quest_data::{CombatStatus, Encounter};
+
+    /// A party of two at the boss fight closing the quest, with the boss already beaten
+    fn quest_at_boss() -> Quest {
+        let mut boss = bestiary::bosses(MonsterFamily::Vermin)[0].spawn(1);
+        for entry in &mut boss.loot {
+            entry.chance = 100;
+        }
+
+        let fight = Combat {
+            monsters: vec![boss],
+            status: CombatStatus::Victory,
+            ..Combat::default()
+        };
+
+        Quest {
+            members: vec![
+                Character::new(1, 1, "Al".to_string(), CharacterClass::Warrior),
+                Character::new(2, 2, "Bo".to_string(), CharacterClass::Mage),
+            ],
+            encounters: [Encounter::EmptyEncounter, Encounter::EmptyEncounter, Encounter::CombatEncounter(fight)],
+            ..Quest::default()
+        }
+    }
+
+    #[test]
+    fn boss_drops_can_be_won() {
+        let mut quest = quest_at_boss();
+        for _ in 0..4 {
+            quest.advance().unwrap();
+        }
+
+        assert_eq!(quest.status, QuestStatus::InProgress);
+        assert!(!quest.loot.is_empty());
+        assert!(quest.advance().is_err());
+
+        for index in 0..quest.loot.len() {
+            quest.roll_for_loot(1, index, RollChoice::Need).unwrap();
+            quest.roll_for_loot(2, index, RollChoice::Pass).unwrap();
+        }
+
+        assert!(quest.loot.iter().all(|x| x.status == LootStatus::Won(1)));
+        assert_eq!(quest.status, QuestStatus::Completed);
+        for drop in &quest.loot {
+            assert!(quest.members[0].inventory.get(&drop.item.name).is_some());
+        }
+    }
+}
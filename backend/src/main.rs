@@ -1,33 +1,50 @@
 use axum::{
-    Extension, Router, extract::State, handler::Handler, response::Json, routing::{get, post}
+    Extension, Router, extract::Path, response::Json, routing::{get, post}
 };
 use color_eyre::Result;
+use ratback::auth::{self, AuthUser};
 use ratback::data::{ServerState, SharedState};
+use ratback::error::ApiError;
+use ratback::storage::{DEFAULT_DB_PATH, Storage};
 
-use serde_json::{Value, json};
-use std::{net::SocketAddr, sync::{Arc, RwLock}};
+use std::{env, env::VarError, fmt::Display, net::SocketAddr, str::FromStr, sync::RwLock};
 use tokio::net::TcpListener;
 
-use ratback::data::{Character, Item, MAX_COMBAT_ENEMIES, MAX_ENCOUNTER_LENGTH, MAX_PARTY_SIZE, User};
-use ratback::quest_data::Quest;
+use ratback::data::{Character, Credentials, Equip, NewCharacter, Session, Unequip, User};
 
 
 #[tokio::main]
 async fn main() -> Result<()> {
 
-    let state = ServerState {
-            users: [None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None],
-            characters: [None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None],
-            quests: [None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None]
-        };
+    let storage = Storage::open(&env::var("RATBACK_DB").unwrap_or(DEFAULT_DB_PATH.to_string()))?;
+    let limit = env_setting("RATBACK_MAX_RECORDS")?;
+
+    let mut state = ServerState::new(storage, limit)?;
+    state.reward_policy = env_setting("RATBACK_REWARD_POLICY")?.unwrap_or_default();
+    if let Some(minutes) = env_setting::<u64>("RATBACK_REGEN_MINUTES")? {
+        state.regen_interval = minutes * 60;
+    }
+
+    check_data("data/riddles.json", ratback::riddles::validate())?;
+    println!("Loaded {} riddles", ratback::riddles::all().len());
+
+    check_data("data/dialogues.json", ratback::dialogues::validate())?;
+    println!("Loaded {} dialogues", ratback::dialogues::all().len());
 
     let x: SharedState = SharedState::new(RwLock::new(state));
 
     let app = Router::new() //with_state(ServerState::default())
         .route("/api/hello-world", get(hello_world))
         .route("/api/register", post(register))
+        .route("/api/login", post(login))
         .route("/api/character", post(create_character))
+        .route("/api/characters", get(list_characters))
+        .route("/api/character/{id}/equip", post(equip))
+        .route("/api/character/{id}/unequip", post(unequip))
         .nest("/api", ratback::quest::routes())
+        .nest("/api", ratback::party::routes())
+        .nest("/api", ratback::shop::routes())
+        .nest("/api", ratback::inn::routes())
         .layer(Extension(x))
         ;
 
@@ -39,47 +56,163 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// The value of the environment variable `name`, none if it isn't set.
+/// A value that doesn't parse stops the server from starting rather than being ignored.
+fn env_setting<T>(name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match env::var(name) {
+        Ok(value) => match value.parse() {
+            Ok(x) => Ok(Some(x)),
+            Err(e) => Err(color_eyre::eyre::eyre!("Invalid {name} {value:?}: {e}")),
+        },
+        Err(VarError::NotPresent) => Ok(None),
+        Err(e) => Err(color_eyre::eyre::eyre!("Invalid {name}: {e}")),
+    }
+}
+
+/// Refuses to start on broken game data, printing every problem found in `file`
+fn check_data(file: &str, validation: Result<(), Vec<String>>) -> Result<()> {
+    if let Err(problems) = validation {
+        for problem in &problems {
+            eprintln!("Invalid data in {file}: {problem}");
+        }
+        return Err(color_eyre::eyre::eyre!("{} problems in {file}", problems.len()));
+    }
+
+    Ok(())
+}
+
 async fn hello_world() -> &'static str {
     "Hello World"
 }
 
-async fn register(Extension(state): Extension<SharedState>, username: String) -> Json<User> {
+async fn register(Extension(state): Extension<SharedState>, Json(credentials): Json<Credentials>) -> Result<Json<User>, ApiError> {
+    let Credentials { username, password } = credentials;
+    let hash = blocking(move || auth::hash_password(&password)).await??;
+
+    let mut state = state.write().unwrap();
+
+    if state.users.values().any(|x| x.username == username) {
+        return Err(ApiError::UsernameTaken(username));
+    }
+
+    let id = state.users.vacant_id()?;
     let usr = User {
-        username: username,
+        id,
+        username,
         ..Default::default()
     };
 
-    println!("Registered user: {}", usr.clone().username);
-    let result = Json(usr.clone());
-
-    for x in state.write().unwrap().users.iter_mut() {
-        match x {
-            None => {
-                *x = Some(usr);
-                break;
-            }
-            _ => {}
-            
-        }
+    state.storage.save_account(id, &usr, &hash)?;
+    state.users.insert(id, usr.clone());
+    state.passwords.insert(id, hash);
+
+    println!("Registered user: {}", usr.username);
+
+    Ok(Json(usr))
+}
+
+async fn login(Extension(state): Extension<SharedState>, Json(credentials): Json<Credentials>) -> Result<Json<Session>, ApiError> {
+    let (user, hash) = {
+        let state = state.read().unwrap();
+
+        let user = state
+            .users
+            .values()
+            .find(|x| x.username == credentials.username)
+            .ok_or(ApiError::InvalidCredentials)?
+            .clone();
+        let hash = state.passwords.get(user.id).ok_or(ApiError::InvalidCredentials)?.clone();
+
+        (user, hash)
+    };
+
+    if !blocking(move || auth::verify_password(&credentials.password, &hash)).await? {
+        return Err(ApiError::InvalidCredentials);
     }
-    
 
-    result
+    let token = auth::new_token();
+    state.write().unwrap().sessions.insert(token.clone(), user.id);
+
+    Ok(Json(Session { token, user }))
 }
 
-async fn create_character(Extension(state): Extension<SharedState>) -> Json<Character> {
-    let chr = Character::new();
-
-    for x in state.write().unwrap().characters.iter_mut() {
-        match x {
-             None => {
-                *x = Some(chr);
-                break;
-            }
-            _ => {}
-            
-        }
+/// Runs slow work like password hashing off the async workers, so it doesn't hold up other requests
+async fn blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> Result<T, ApiError> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))
+}
+
+async fn create_character(Extension(state): Extension<SharedState>, user: AuthUser, Json(new_character): Json<NewCharacter>) -> Result<Json<Character>, ApiError> {
+    let name = new_character.name.trim().to_string();
+    if name.is_empty() {
+        return Err(ApiError::BadRequest("Characters need a name".to_string()));
     }
 
-    Json(chr)
+    let mut state = state.write().unwrap();
+
+    let mut owner = state.users.get(user.id).ok_or(ApiError::Unauthorized)?.clone();
+    let id = state.characters.vacant_id()?;
+    let mut chr = Character::new(id, user.id, name, new_character.class);
+    chr.resting_since = Some(ratback::inn::now());
+    owner.character_ids.push(id);
+
+    state.storage.save_character(id, &chr)?;
+    state.storage.save_user(owner.id, &owner)?;
+    state.characters.insert(id, chr.clone());
+    state.users.insert(owner.id, owner);
+
+    Ok(Json(chr))
+}
+
+async fn list_characters(Extension(state): Extension<SharedState>, user: AuthUser) -> Result<Json<Vec<Character>>, ApiError> {
+    let state = state.read().unwrap();
+    let owner = state.users.get(user.id).ok_or(ApiError::Unauthorized)?;
+
+    let characters = owner
+        .character_ids
+        .iter()
+        .filter_map(|id| state.characters.get(*id))
+        .cloned()
+        .map(|mut x| {
+            state.regenerate(&mut x);
+            x
+        })
+        .collect();
+
+    Ok(Json(characters))
+}
+
+async fn equip(Extension(state): Extension<SharedState>, user: AuthUser, Path(id): Path<u32>, Json(equip): Json<Equip>) -> Result<Json<Character>, ApiError> {
+    let mut state = state.write().unwrap();
+
+    let mut chr = state.rested_character(user.id, id)?;
+    if state.on_quest(id) {
+        return Err(ApiError::BadRequest(format!("{} is away on a quest", chr.name)));
+    }
+    chr.equip(&equip.item)?;
+
+    state.storage.save_character(id, &chr)?;
+    state.characters.insert(id, chr.clone());
+
+    Ok(Json(chr))
+}
+
+async fn unequip(Extension(state): Extension<SharedState>, user: AuthUser, Path(id): Path<u32>, Json(unequip): Json<Unequip>) -> Result<Json<Character>, ApiError> {
+    let mut state = state.write().unwrap();
+
+    let mut chr = state.rested_character(user.id, id)?;
+    if state.on_quest(id) {
+        return Err(ApiError::BadRequest(format!("{} is away on a quest", chr.name)));
+    }
+    chr.unequip(unequip.slot)?;
+
+    state.storage.save_character(id, &chr)?;
+    state.characters.insert(id, chr.clone());
+
+    Ok(Json(chr))
 }
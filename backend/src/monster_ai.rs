@@ -0,0 +1,152 @@
+use crate::bestiary::{Behaviour, Monster};
+use crate::data::Character;
+
+pub const HEAL_ENERGY_COST: i32 = 2;
+
+/// What a monster does on its turn, targets are indexes into the party or `Combat.monsters`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MonsterMove {
+    Attack { target: usize, damage: i32 },
+    AttackAll { damage: i32 },
+    Heal { target: usize, amount: i32 },
+    Flee,
+    Wait,
+}
+
+/// Picks the move of the monster at `index`, given everyone still in the fight
+pub trait Strategy {
+    fn choose(&self, index: usize, monsters: &[Monster], party: &[Character]) -> MonsterMove;
+}
+
+/// Hits whoever stands at the front of the party
+pub struct Aggressive;
+
+/// Goes for the party member closest to being knocked out
+pub struct LowestHealth;
+
+/// Patches up wounded allies, and only fights when nobody needs healing
+pub struct Healer;
+
+/// Runs away once badly hurt
+pub struct Fleeing;
+
+/// Gets more dangerous the lower its health, ending by lashing out at the whole party
+pub struct PhasedBoss;
+
+impl Behaviour {
+    pub fn strategy(self) -> &'static dyn Strategy {
+        match self {
+            Behaviour::Aggressive => &Aggressive,
+            Behaviour::Cunning => &LowestHealth,
+            Behaviour::Support => &Healer,
+            Behaviour::Cowardly => &Fleeing,
+            Behaviour::Boss => &PhasedBoss,
+        }
+    }
+}
+
+impl Monster {
+    /// Still in the fight, neither defeated nor fled
+    pub fn is_active(&self) -> bool {
+        self.unit.stats.health > 0 && !self.fled
+    }
+
+    /// The boss phase the monster is in, from 1 at full health up to 3 below a quarter of it
+    pub fn phase(&self) -> u8 {
+        let health = self.unit.stats.health * 4;
+        let max = self.unit.max_stats.health;
+
+        if health <= max {
+            3
+        } else if health <= max * 2 {
+            2
+        } else {
+            1
+        }
+    }
+}
+
+impl Strategy for Aggressive {
+    fn choose(&self, index: usize, monsters: &[Monster], party: &[Character]) -> MonsterMove {
+        match party.iter().position(|x| x.unit.stats.health > 0) {
+            Some(target) => MonsterMove::Attack {
+                target,
+                damage: monsters[index].damage,
+            },
+            None => MonsterMove::Wait,
+        }
+    }
+}
+
+impl Strategy for LowestHealth {
+    fn choose(&self, index: usize, monsters: &[Monster], party: &[Character]) -> MonsterMove {
+        let target = party
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.unit.stats.health > 0)
+            .min_by_key(|(_, x)| x.unit.stats.health)
+            .map(|(i, _)| i);
+
+        match target {
+            Some(target) => MonsterMove::Attack {
+                target,
+                damage: monsters[index].damage,
+            },
+            None => MonsterMove::Wait,
+        }
+    }
+}
+
+impl Strategy for Healer {
+    fn choose(&self, index: usize, monsters: &[Monster], party: &[Character]) -> MonsterMove {
+        if monsters[index].unit.stats.energy < HEAL_ENERGY_COST {
+            return Aggressive.choose(index, monsters, party);
+        }
+
+        // The ally missing the largest share of its health
+        let wounded = monsters
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.is_active() && x.unit.stats.health < x.unit.max_stats.health)
+            .min_by_key(|(_, x)| x.unit.stats.health * 100 / x.unit.max_stats.health.max(1))
+            .map(|(i, _)| i);
+
+        match wounded {
+            Some(target) => MonsterMove::Heal {
+                target,
+                amount: monsters[index].damage * 2,
+            },
+            None => Aggressive.choose(index, monsters, party),
+        }
+    }
+}
+
+impl Strategy for Fleeing {
+    fn choose(&self, index: usize, monsters: &[Monster], party: &[Character]) -> MonsterMove {
+        let monster = &monsters[index];
+
+        if monster.unit.stats.health * 3 <= monster.unit.max_stats.health {
+            MonsterMove::Flee
+        } else {
+            Aggressive.choose(index, monsters, party)
+        }
+    }
+}
+
+impl Strategy for PhasedBoss {
+    fn choose(&self, index: usize, monsters: &[Monster], party: &[Character]) -> MonsterMove {
+        let monster = &monsters[index];
+
+        match monster.phase() {
+            1 => Aggressive.choose(index, monsters, party),
+            2 => match LowestHealth.choose(index, monsters, party) {
+                MonsterMove::Attack { target, damage } => MonsterMove::Attack {
+                    target,
+                    damage: damage + damage / 2,
+                },
+                other => other,
+            },
+            _ => MonsterMove::AttackAll { damage: monster.damage },
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use axum::{
+    Extension, Json, Router,
+    extract::Path,
+    routing::{get, post},
+};
+
+use crate::auth::AuthUser;
+use crate::data::{JoinParty, MAX_PARTY_SIZE, Party, PartyInvite, PartyLobby, PartyMember, ServerState, SharedState};
+use crate::error::ApiError;
+
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/party", get(get_lobby).post(create_party))
+        .route("/party/invite", post(invite))
+        .route("/party/{id}/accept", post(accept_invite))
+        .route("/party/leave", post(leave_party))
+}
+
+fn lobby_of(state: &ServerState, user: AuthUser) -> Result<PartyLobby, ApiError> {
+    let username = &state.users.get(user.id).ok_or(ApiError::Unauthorized)?.username;
+
+    Ok(PartyLobby {
+        party: state.party_of(user.id).cloned(),
+        invites: state
+            .parties
+            .values()
+            .filter(|x| x.invited.contains(username))
+            .cloned()
+            .collect(),
+    })
+}
+
+async fn get_lobby(Extension(state): Extension<SharedState>, user: AuthUser) -> Result<Json<PartyLobby>, ApiError> {
+    Ok(Json(lobby_of(&state.read().unwrap(), user)?))
+}
+
+async fn create_party(Extension(state): Extension<SharedState>, user: AuthUser, Json(join): Json<JoinParty>) -> Result<Json<Party>, ApiError> {
+    let mut state = state.write().unwrap();
+
+    if state.party_of(user.id).is_some() {
+        return Err(ApiError::BadRequest("You are already in a party".to_string()));
+    }
+
+    state.owned_character(user.id, join.character_id)?;
+    let username = state.users.get(user.id).ok_or(ApiError::Unauthorized)?.username.clone();
+
+    let id = state.parties.vacant_id()?;
+    let party = Party {
+        id,
+        leader: user.id,
+        members: vec![PartyMember {
+            user_id: user.id,
+            username,
+            character_id: join.character_id,
+        }],
+        invited: vec![],
+        quest_id: None,
+    };
+    state.parties.insert(id, party.clone());
+
+    Ok(Json(party))
+}
+
+async fn invite(Extension(state): Extension<SharedState>, user: AuthUser, Json(invite): Json<PartyInvite>) -> Result<Json<Party>, ApiError> {
+    let mut state = state.write().unwrap();
+
+    let mut party = state
+        .party_of(user.id)
+        .ok_or(ApiError::NotFound("your party".to_string()))?
+        .clone();
+
+    if party.leader != user.id {
+        return Err(ApiError::Forbidden("Only the party leader can invite".to_string()));
+    }
+
+    let invitee = state
+        .users
+        .values()
+        .find(|x| x.username == invite.username)
+        .ok_or(ApiError::NotFound(format!("user {}", invite.username)))?;
+
+    if party.members.iter().any(|x| x.user_id == invitee.id) || party.invited.contains(&invitee.username) {
+        return Err(ApiError::BadRequest(format!("{} is already in the party", invitee.username)));
+    }
+
+    if party.members.len() + party.invited.len() >= MAX_PARTY_SIZE {
+        return Err(ApiError::BadRequest(format!("Parties can't have more than {MAX_PARTY_SIZE} members")));
+    }
+
+    party.invited.push(invitee.username.clone());
+    state.parties.insert(party.id, party.clone());
+
+    Ok(Json(party))
+}
+
+async fn accept_invite(Extension(state): Extension<SharedState>, user: AuthUser, Path(id): Path<u32>, Json(join): Json<JoinParty>) -> Result<Json<Party>, ApiError> {
+    let mut state = state.write().unwrap();
+
+    if state.party_of(user.id).is_some() {
+        return Err(ApiError::BadRequest("You are already in a party".to_string()));
+    }
+
+    state.owned_character(user.id, join.character_id)?;
+    let username = state.users.get(user.id).ok_or(ApiError::Unauthorized)?.username.clone();
+
+    let party = state
+        .parties
+        .get_mut(id)
+        .filter(|x| x.invited.contains(&username))
+        .ok_or(ApiError::NotFound(format!("an invite to party {id}")))?;
+
+    party.invited.retain(|x| *x != username);
+    party.members.push(PartyMember {
+        user_id: user.id,
+        username,
+        character_id: join.character_id,
+    });
+
+    Ok(Json(party.clone()))
+}
+
+/// Leaves the caller's party, disbanding it when the leader leaves.
+/// Nobody can leave while the party is out on a quest, it has to be finished or given up first.
+async fn leave_party(Extension(state): Extension<SharedState>, user: AuthUser) -> Result<Json<PartyLobby>, ApiError> {
+    let mut state = state.write().unwrap();
+
+    let mut party = state
+        .party_of(user.id)
+        .ok_or(ApiError::NotFound("your party".to_string()))?
+        .clone();
+
+    let on_quest = party
+        .quest_id
+        .and_then(|id| state.quests.get(id))
+        .is_some_and(|x| !x.status.is_finished());
+    if on_quest {
+        return Err(ApiError::BadRequest("Your party is still on a quest, give it up before leaving".to_string()));
+    }
+
+    if party.leader == user.id {
+        state.parties.remove(party.id);
+    } else {
+        party.members.retain(|x| x.user_id != user.id);
+        state.parties.insert(party.id, party);
+    }
+
+    Ok(Json(lobby_of(&state, user)?))
+}
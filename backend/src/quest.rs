@@ -1,38 +1,284 @@
+use axum::{
+    Extension, Json, Router,
+    extract::Path,
+    routing::{get, post},
+};
 
-use axum::{Extension, Json, Router, extract::State, routing::post};
-use serde_json::json;
-
-use crate::data::{Character, SharedState, ServerState};
-use crate::quest_data::Quest;
+use crate::auth::AuthUser;
+use crate::data::SharedState;
+use crate::error::ApiError;
+use crate::combat;
+use crate::quest_data::{CombatRequest, DialogueChoice, Encounter, LootChoice, NewQuest, Quest, QuestStatus, RiddleAnswer, TurnAction};
+use crate::quest_gen;
 
 
 pub fn routes() -> Router {
     Router::new()
         .route("/quest", post(init_quest))
-        .route("/combat", post(init_combat))
+        .route("/quests", get(list_quests))
+        .route("/quest/{id}", get(get_quest))
+        .route("/quest/{id}/advance", post(advance_quest))
+        .route("/quest/{id}/answer", post(answer_riddle))
+        .route("/quest/{id}/respond", post(respond_to_npc))
+        .route("/quest/{id}/loot", post(roll_for_loot))
+        .route("/quest/{id}/abandon", post(abandon_quest))
+        .route("/quest/{id}/end-turn", post(end_turn))
+        .route("/combat", post(combat_action))
 }
 
-async fn init_quest(Extension(state): Extension<SharedState>) -> Json<Quest> {
-    let mut quest = Quest::default();
+/// Starts a quest for the caller's party, only its leader can do this
+async fn init_quest(Extension(state): Extension<SharedState>, user: AuthUser, new_quest: Option<Json<NewQuest>>) -> Result<Json<Quest>, ApiError> {
+    let mut state = state.write().unwrap();
+
+    let party = state
+        .party_of(user.id)
+        .ok_or(ApiError::NotFound("your party".to_string()))?;
+
+    if party.leader != user.id {
+        return Err(ApiError::Forbidden("Only the party leader can start a quest".to_string()));
+    }
+
+    let on_quest = party
+        .quest_id
+        .and_then(|id| state.quests.get(id))
+        .is_some_and(|x| !x.status.is_finished());
+    if on_quest {
+        return Err(ApiError::BadRequest("Your party is already on a quest".to_string()));
+    }
+
+    let members = party
+        .members
+        .iter()
+        .map(|x| state.rested_character(x.user_id, x.character_id))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // A character can't be out on two quests, whatever party it was in for the other one
+    if let Some(busy) = members.iter().find(|x| state.on_quest(x.id)) {
+        return Err(ApiError::BadRequest(format!("{} is already on a quest", busy.name)));
+    }
+
+    let party_id = party.id;
+    let id = state.quests.vacant_id()?;
+    let seed = new_quest.and_then(|x| x.seed).unwrap_or_else(rand::random);
+    let party_level = members.iter().map(|x| x.level()).sum::<u32>() / members.len() as u32;
 
-    let mut j = 0;
-    for c in state.write().unwrap().characters.iter() {
-        match c {
-            Some(ch) => {
-                quest.members[j] = *ch;
-                j += 1;
-            }
-            None => break,
-        }
+    let quest = Quest {
+        id,
+        owner: user.id,
+        status: QuestStatus::Created,
+        seed,
+        encounters: quest_gen::generate_encounters(seed, party_level, members.len()),
+        members,
+        reward_policy: state.reward_policy,
+        ..Default::default()
+    };
 
-        if j >= 3 {
-            break;
-        }
+    state.storage.save_quest(id, &quest)?;
+    state.quests.insert(id, quest.clone());
+    if let Some(party) = state.parties.get_mut(party_id) {
+        party.quest_id = Some(id);
     }
 
-    Json(quest)
+    Ok(Json(quest))
+}
+
+async fn get_quest(Extension(state): Extension<SharedState>, user: AuthUser, Path(id): Path<u32>) -> Result<Json<Quest>, ApiError> {
+    let state = state.read().unwrap();
+
+    match state.quests.get(id) {
+        Some(quest) if quest.has_member(user.id) => Ok(Json(quest.clone())),
+        _ => Err(ApiError::NotFound(format!("quest {id}"))),
+    }
 }
 
-async fn init_combat(Extension(state): Extension<SharedState>) -> Json<Quest> {
-    Json(Quest::default())
+/// Every quest one of the caller's characters went on, oldest first
+async fn list_quests(Extension(state): Extension<SharedState>, user: AuthUser) -> Result<Json<Vec<Quest>>, ApiError> {
+    let state = state.read().unwrap();
+    let quests = state.quests.values().filter(|x| x.has_member(user.id)).cloned().collect();
+
+    Ok(Json(quests))
+}
+
+/// Moves the party on to the next encounter, only the quest owner can do this
+async fn advance_quest(Extension(state): Extension<SharedState>, user: AuthUser, Path(id): Path<u32>) -> Result<Json<Quest>, ApiError> {
+    let mut state = state.write().unwrap();
+
+    let mut quest = match state.quests.get(id) {
+        Some(quest) if quest.has_member(user.id) => quest.clone(),
+        _ => return Err(ApiError::NotFound(format!("quest {id}"))),
+    };
+
+    if quest.owner != user.id {
+        return Err(ApiError::Forbidden("Only the party leader can lead the way".to_string()));
+    }
+
+    quest.advance()?;
+    if quest.status.is_finished() {
+        state.return_from_quest(&quest)?;
+    }
+
+    state.storage.save_quest(id, &quest)?;
+    state.quests.insert(id, quest.clone());
+
+    Ok(Json(quest))
+}
+
+/// Answers the riddle the quest is at, any member of the party can try
+async fn answer_riddle(Extension(state): Extension<SharedState>, user: AuthUser, Path(id): Path<u32>, Json(answer): Json<RiddleAnswer>) -> Result<Json<Quest>, ApiError> {
+    let mut state = state.write().unwrap();
+
+    let mut quest = match state.quests.get(id) {
+        Some(quest) if quest.has_member(user.id) => quest.clone(),
+        _ => return Err(ApiError::NotFound(format!("quest {id}"))),
+    };
+
+    quest.answer_riddle(&answer.answer)?;
+
+    state.storage.save_quest(id, &quest)?;
+    state.quests.insert(id, quest.clone());
+
+    Ok(Json(quest))
+}
+
+/// Responds to the stranger the quest is at, any member of the party can speak up
+async fn respond_to_npc(Extension(state): Extension<SharedState>, user: AuthUser, Path(id): Path<u32>, Json(response): Json<DialogueChoice>) -> Result<Json<Quest>, ApiError> {
+    let mut state = state.write().unwrap();
+
+    let mut quest = match state.quests.get(id) {
+        Some(quest) if quest.has_member(user.id) => quest.clone(),
+        _ => return Err(ApiError::NotFound(format!("quest {id}"))),
+    };
+
+    let character_id = quest
+        .members
+        .iter()
+        .find(|x| x.owner == user.id)
+        .map(|x| x.id)
+        .ok_or(ApiError::NotFound(format!("your character in quest {id}")))?;
+
+    quest.respond(character_id, response.choice)?;
+
+    state.storage.save_quest(id, &quest)?;
+    state.quests.insert(id, quest.clone());
+
+    Ok(Json(quest))
+}
+
+/// Rolls need, greed or pass for the caller's character on a drop in the loot pool.
+/// After the last encounter, the last roll ends the quest.
+async fn roll_for_loot(Extension(state): Extension<SharedState>, user: AuthUser, Path(id): Path<u32>, Json(choice): Json<LootChoice>) -> Result<Json<Quest>, ApiError> {
+    let mut state = state.write().unwrap();
+
+    let mut quest = match state.quests.get(id) {
+        Some(quest) if quest.has_member(user.id) => quest.clone(),
+        _ => return Err(ApiError::NotFound(format!("quest {id}"))),
+    };
+
+    let character_id = quest
+        .members
+        .iter()
+        .find(|x| x.owner == user.id)
+        .map(|x| x.id)
+        .ok_or(ApiError::NotFound(format!("your character in quest {id}")))?;
+
+    quest.roll_for_loot(character_id, choice.drop, choice.choice)?;
+    if quest.status.is_finished() {
+        state.return_from_quest(&quest)?;
+    }
+
+    state.storage.save_quest(id, &quest)?;
+    state.quests.insert(id, quest.clone());
+
+    Ok(Json(quest))
+}
+
+/// Gives up on the quest and brings the party home, any member can call it off
+async fn abandon_quest(Extension(state): Extension<SharedState>, user: AuthUser, Path(id): Path<u32>) -> Result<Json<Quest>, ApiError> {
+    let mut state = state.write().unwrap();
+
+    let mut quest = match state.quests.get(id) {
+        Some(quest) if quest.has_member(user.id) => quest.clone(),
+        _ => return Err(ApiError::NotFound(format!("quest {id}"))),
+    };
+
+    quest.abandon()?;
+    state.return_from_quest(&quest)?;
+
+    state.storage.save_quest(id, &quest)?;
+    state.quests.insert(id, quest.clone());
+
+    Ok(Json(quest))
+}
+
+/// Chooses the caller's action for the next turn of the fight the quest is in
+async fn combat_action(Extension(state): Extension<SharedState>, user: AuthUser, Json(request): Json<CombatRequest>) -> Result<Json<Quest>, ApiError> {
+    let mut state = state.write().unwrap();
+    let id = request.quest_id;
+
+    let mut quest = match state.quests.get(id) {
+        Some(quest) if quest.has_member(user.id) => quest.clone(),
+        _ => return Err(ApiError::NotFound(format!("quest {id}"))),
+    };
+
+    let character_id = quest
+        .members
+        .iter()
+        .find(|x| x.owner == user.id)
+        .map(|x| x.id)
+        .ok_or(ApiError::NotFound(format!("your character in quest {id}")))?;
+
+    let combat = match &mut quest.open_encounter {
+        Some(Encounter::CombatEncounter(combat)) if quest.status == QuestStatus::InProgress => combat,
+        _ => return Err(ApiError::BadRequest("The party isn't in a fight".to_string())),
+    };
+
+    let action = TurnAction {
+        character_id,
+        action: request.action,
+        target: request.target,
+    };
+    combat::submit_action(&mut quest.members, combat, action)?;
+    quest.check_wipe()?;
+    if quest.status.is_finished() {
+        state.return_from_quest(&quest)?;
+    }
+
+    state.storage.save_quest(id, &quest)?;
+    state.quests.insert(id, quest.clone());
+
+    Ok(Json(quest))
+}
+
+/// Ends the turn of the fight the quest is in for the members still making up their minds.
+/// The caller has to have chosen an action already.
+async fn end_turn(Extension(state): Extension<SharedState>, user: AuthUser, Path(id): Path<u32>) -> Result<Json<Quest>, ApiError> {
+    let mut state = state.write().unwrap();
+
+    let mut quest = match state.quests.get(id) {
+        Some(quest) if quest.has_member(user.id) => quest.clone(),
+        _ => return Err(ApiError::NotFound(format!("quest {id}"))),
+    };
+
+    let character_id = quest
+        .members
+        .iter()
+        .find(|x| x.owner == user.id)
+        .map(|x| x.id)
+        .ok_or(ApiError::NotFound(format!("your character in quest {id}")))?;
+
+    let combat = match &mut quest.open_encounter {
+        Some(Encounter::CombatEncounter(combat)) if quest.status == QuestStatus::InProgress => combat,
+        _ => return Err(ApiError::BadRequest("The party isn't in a fight".to_string())),
+    };
+
+    combat::end_turn(&mut quest.members, combat, character_id)?;
+    quest.check_wipe()?;
+    if quest.status.is_finished() {
+        state.return_from_quest(&quest)?;
+    }
+
+    state.storage.save_quest(id, &quest)?;
+    state.quests.insert(id, quest.clone());
+
+    Ok(Json(quest))
 }
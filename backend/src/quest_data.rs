@@ -1,11 +1,78 @@
-use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
-use crate::data::{Character, Item, MAX_COMBAT_ENEMIES, MAX_ENCOUNTER_LENGTH, MAX_PARTY_SIZE};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::bestiary::Monster;
+use crate::data::{Character, Item, MAX_ENCOUNTER_LENGTH};
+use crate::dialogues::{self, DialogueDefinition, DialogueNode, DialogueOutcome};
+use crate::error::ApiError;
+use crate::items;
+use crate::level::LevelUp;
+use crate::quest_gen;
+use crate::riddles;
+use crate::status::{Combatant, StatusEffect};
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Combat {
-    pub monsters: [Character; MAX_COMBAT_ENEMIES],
+    /// Up to `MAX_COMBAT_ENEMIES` monsters
+    pub monsters: Vec<Monster>,
+    pub turn: u16,
+    #[serde(default)]
+    pub status: CombatStatus,
+    /// Actions chosen for the coming turn
+    #[serde(default)]
+    pub pending: Vec<TurnAction>,
+    #[serde(default)]
+    pub log: Vec<CombatLogEntry>,
+    /// Status effects on anyone in the fight
+    #[serde(default)]
+    pub effects: Vec<StatusEffect>,
+    /// Skills waiting to be usable again
+    #[serde(default)]
+    pub cooldowns: Vec<Cooldown>,
+    /// Drives the rolls of every turn, so a fight plays out the same way when replayed
+    #[serde(default)]
+    pub seed: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CombatStatus {
+    #[default]
+    Ongoing,
+    Victory,
+    Wipe,
+}
+
+/// `skill` can be used again from turn `ready_turn` on
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Cooldown {
+    pub character_id: u32,
+    pub skill: String,
+    pub ready_turn: u16,
+}
+
+/// The action a party member takes this turn, `target` is an index into `Combat.monsters`,
+/// or the character id of the ally for skills aimed at one
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TurnAction {
+    pub character_id: u32,
+    pub action: CombatAction,
+    pub target: usize,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CombatLogEntry {
     pub turn: u16,
+    pub message: String,
+}
+
+/// What the client sends to act in the fight of a quest
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CombatRequest {
+    pub quest_id: u32,
+    pub action: CombatAction,
+    pub target: usize,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -13,7 +80,96 @@ pub enum Encounter {
     #[default]
     EmptyEncounter,
     CombatEncounter(Combat),
-    NpcEncounter(EncounterReward),
+    NpcEncounter(#[serde(deserialize_with = "npc_or_gift")] Npc),
+    RiddleEncounter(Riddle),
+}
+
+/// A stranger the party talks to. The dialogue tree stays on the server, in `dialogues::find(&dialogue)`,
+/// the client only sees what is being said right now.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Npc {
+    pub dialogue: String,
+    pub name: String,
+    /// The node of the dialogue the conversation is at
+    pub node: String,
+    /// What the stranger is saying, empty once the conversation is over
+    pub text: String,
+    /// The responses the party can pick from, by index
+    pub choices: Vec<String>,
+    pub status: DialogueStatus,
+    /// Every line said so far
+    #[serde(default)]
+    pub transcript: Vec<String>,
+    /// What the conversation ended with
+    pub reward: EncounterReward,
+    /// Rewards and prices in the dialogue are per party level
+    pub level: u32,
+    pub party_size: usize,
+    /// Drives the fight if the conversation turns sour
+    pub seed: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DialogueStatus {
+    #[default]
+    Talking,
+    Over,
+}
+
+/// What the client sends to respond to the stranger of a quest, an index into `Npc.choices`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DialogueChoice {
+    pub choice: usize,
+}
+
+/// Quests saved before strangers could talk hold only the stranger's gift
+fn npc_or_gift<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Npc, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Saved {
+        Npc(Npc),
+        Gift(EncounterReward),
+    }
+
+    Ok(match Saved::deserialize(deserializer)? {
+        Saved::Npc(npc) => npc,
+        Saved::Gift(reward) => Npc {
+            name: "A friendly stranger".to_string(),
+            status: DialogueStatus::Over,
+            reward,
+            ..Npc::default()
+        },
+    })
+}
+
+/// A riddle the party has to answer. The accepted answers stay on the server, in `riddles::find(&id)`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Riddle {
+    pub id: String,
+    pub question: String,
+    pub attempts_left: u8,
+    pub status: RiddleStatus,
+    /// Wrong answers given so far
+    #[serde(default)]
+    pub guesses: Vec<String>,
+    /// Earned by solving the riddle
+    pub reward: EncounterReward,
+    /// Paid for running out of attempts or giving up
+    pub penalty: EncounterReward,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RiddleStatus {
+    #[default]
+    Unsolved,
+    Solved,
+    Failed,
+}
+
+/// What the client sends to answer the riddle of a quest
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RiddleAnswer {
+    pub answer: String,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -23,16 +179,590 @@ pub enum EncounterReward {
     CoinReward(u32),
     ExperienceReward(u32),
     CoinAndExperienceReward(u32, u32),
+    /// Coins the party loses
+    CoinPenalty(u32),
+    ItemReward(Item),
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EncounterOutcome {
+    pub encounter: usize,
+    pub description: String,
+    pub reward: EncounterReward,
+    /// What the defeated monsters dropped
+    #[serde(default)]
+    pub loot: Vec<Item>,
+}
+
+/// An item found on a quest, waiting for every member to roll for it
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LootDrop {
+    pub item: Item,
+    pub rolls: Vec<LootRoll>,
+    pub status: LootStatus,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LootStatus {
+    #[default]
+    Rolling,
+    /// Handed to the character with this id
+    Won(u32),
+    /// Everyone passed, the item is left behind
+    Passed,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LootRoll {
+    pub character_id: u32,
+    pub choice: RollChoice,
+    /// 1 to 100, 0 for a pass
+    pub roll: u32,
+}
+
+/// Need beats greed no matter the roll, passing opts out of the drop
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RollChoice {
+    Need,
+    Greed,
+    #[default]
+    Pass,
+}
+
+/// What the client sends to roll for a drop in the loot pool of a quest, `drop` is an index into `Quest.loot`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LootChoice {
+    pub drop: usize,
+    pub choice: RollChoice,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct QuestSummary {
+    pub status: QuestStatus,
+    pub encounters_cleared: usize,
+    pub coins: u32,
+    pub experience: u32,
+}
+
+/// What the client sends to start a quest, leave out the seed for a random quest
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct NewQuest {
+    pub seed: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuestStatus {
+    #[default]
+    Created,
+    InProgress,
+    Completed,
+    Failed,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Quest {
-    pub members: [Character; MAX_PARTY_SIZE],
+    #[serde(default)]
+    pub id: u32,
+    #[serde(default)]
+    pub owner: u32,
+    #[serde(default)]
+    pub status: QuestStatus,
+    /// The seed the encounters were generated from
+    #[serde(default)]
+    pub seed: u64,
+    pub members: Vec<Character>,
     pub encounters: [Encounter; MAX_ENCOUNTER_LENGTH],
     pub open_encounter: Option<Encounter>,
+    /// Index into `encounters` of the open encounter
+    #[serde(default)]
+    pub encounter_index: usize,
+    /// What happened in every encounter closed so far
+    #[serde(default)]
+    pub history: Vec<EncounterOutcome>,
+    /// Filled in once the quest is over
+    #[serde(default)]
+    pub summary: Option<QuestSummary>,
+    /// Levels the members reached while on this quest
+    #[serde(default)]
+    pub level_ups: Vec<LevelUp>,
+    /// How rewards are handed out to the members, fixed when the quest starts
+    #[serde(default)]
+    pub reward_policy: RewardPolicy,
+    /// Items found on the quest that the members roll for
+    #[serde(default)]
+    pub loot: Vec<LootDrop>,
+    /// How the quest ends once the members are done rolling for the loot pool
+    #[serde(default)]
+    pub ending: Option<QuestStatus>,
+}
+
+/// How the coins, experience and items of an encounter are shared by the party
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RewardPolicy {
+    /// Every member gets an even part, the first members get what doesn't divide evenly.
+    /// Items go into the loot pool.
+    #[default]
+    Split,
+    /// Every member gets the full amount and a copy of every item
+    Duplicate,
+}
+
+impl Encounter {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encounter::EmptyEncounter => "Quiet tunnels",
+            Encounter::CombatEncounter(_) => "Combat",
+            Encounter::NpcEncounter(_) => "A stranger",
+            Encounter::RiddleEncounter(_) => "A riddle",
+        }
+    }
+
+    /// Closes the encounter and tells what came out of it
+    fn resolve(&self, index: usize) -> EncounterOutcome {
+        let mut loot = Vec::new();
+        let (description, reward) = match self {
+            Encounter::EmptyEncounter => ("The party sneaks through quiet tunnels".to_string(), EncounterReward::NoReward),
+            Encounter::CombatEncounter(combat) => match combat.status {
+                CombatStatus::Wipe => (format!("The party falls after {} turns", combat.turn), EncounterReward::NoReward),
+                _ => {
+                    // Monsters that ran off aren't worth any experience
+                    let defeated = combat.monsters.iter().filter(|x| !x.fled);
+                    let count = defeated.clone().count();
+                    let plural = if count == 1 { "" } else { "s" };
+                    let experience = defeated.map(|x| x.experience).sum();
+                    loot = combat.roll_loot();
+                    let found = if loot.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" and finds {}", loot.iter().map(|x| x.name.as_str()).collect::<Vec<_>>().join(", "))
+                    };
+                    (
+                        format!("The party defeats {count} monster{plural} in {} turns{found}", combat.turn),
+                        EncounterReward::ExperienceReward(experience),
+                    )
+                }
+            },
+            Encounter::NpcEncounter(npc) => {
+                let description = match (npc.status, &npc.reward) {
+                    (DialogueStatus::Talking, _) => format!("The party walks away from {}", npc.name),
+                    (_, EncounterReward::NoReward) => format!("The party parts ways with {}", npc.name),
+                    (_, EncounterReward::CoinPenalty(_)) => format!("The party trades with {} and pays {}", npc.name, npc.reward),
+                    (_, reward) => format!("{} hands over {reward}", npc.name),
+                };
+                let reward = match npc.status {
+                    DialogueStatus::Talking => EncounterReward::NoReward,
+                    DialogueStatus::Over => npc.reward.clone(),
+                };
+                (description, reward)
+            }
+            Encounter::RiddleEncounter(riddle) => match riddle.status {
+                RiddleStatus::Solved => (format!("The party solves the riddle and earns {}", riddle.reward), riddle.reward.clone()),
+                _ => (format!("The party is stumped by the riddle and loses {}", riddle.penalty), riddle.penalty.clone()),
+            },
+        };
+
+        EncounterOutcome {
+            encounter: index,
+            description,
+            reward,
+            loot,
+        }
+    }
+}
+
+impl EncounterReward {
+    pub fn coins(&self) -> u32 {
+        match self {
+            EncounterReward::CoinReward(coins) | EncounterReward::CoinAndExperienceReward(coins, _) => *coins,
+            _ => 0,
+        }
+    }
+
+    pub fn coin_penalty(&self) -> u32 {
+        match self {
+            EncounterReward::CoinPenalty(coins) => *coins,
+            _ => 0,
+        }
+    }
+
+    /// The same reward with every amount multiplied by `level`
+    pub fn scaled(&self, level: u32) -> EncounterReward {
+        match self {
+            EncounterReward::NoReward => EncounterReward::NoReward,
+            EncounterReward::CoinReward(coins) => EncounterReward::CoinReward(coins * level),
+            EncounterReward::ExperienceReward(xp) => EncounterReward::ExperienceReward(xp * level),
+            EncounterReward::CoinAndExperienceReward(coins, xp) => EncounterReward::CoinAndExperienceReward(coins * level, xp * level),
+            EncounterReward::CoinPenalty(coins) => EncounterReward::CoinPenalty(coins * level),
+            EncounterReward::ItemReward(item) => EncounterReward::ItemReward(item.clone()),
+        }
+    }
+
+    pub fn experience(&self) -> u32 {
+        match self {
+            EncounterReward::ExperienceReward(xp) | EncounterReward::CoinAndExperienceReward(_, xp) => *xp,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for EncounterReward {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncounterReward::NoReward => write!(f, "nothing"),
+            EncounterReward::CoinReward(coins) => write!(f, "{coins} coins"),
+            EncounterReward::ExperienceReward(xp) => write!(f, "{xp} experience"),
+            EncounterReward::CoinAndExperienceReward(coins, xp) => write!(f, "{coins} coins and {xp} experience"),
+            EncounterReward::CoinPenalty(coins) => write!(f, "{coins} coins"),
+            EncounterReward::ItemReward(item) if item.quantity == 1 => write!(f, "{}", item.name),
+            EncounterReward::ItemReward(item) => write!(f, "{} {}", item.quantity, item.name),
+        }
+    }
+}
+
+impl RewardPolicy {
+    /// The part of `amount` the member at `index` of `members` gets
+    pub fn share(self, amount: u32, members: usize, index: usize) -> u32 {
+        match self {
+            RewardPolicy::Split if members > 0 => {
+                let members = members as u32;
+                amount / members + u32::from((index as u32) < amount % members)
+            }
+            RewardPolicy::Split => 0,
+            RewardPolicy::Duplicate => amount,
+        }
+    }
+}
+
+impl FromStr for RewardPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "split" => Ok(RewardPolicy::Split),
+            "duplicate" => Ok(RewardPolicy::Duplicate),
+            _ => Err(format!("unknown reward policy {s}")),
+        }
+    }
+}
+
+impl QuestStatus {
+    /// Whether the quest is over, one way or another
+    pub fn is_finished(&self) -> bool {
+        matches!(self, QuestStatus::Completed | QuestStatus::Failed)
+    }
+}
+
+impl Combat {
+    /// The status effects currently on `target`
+    pub fn effects_on(&self, target: Combatant) -> impl Iterator<Item = &StatusEffect> {
+        self.effects.iter().filter(move |x| x.target == target)
+    }
+
+    /// The turn on which `skill` is usable again for the character, if it's on cooldown
+    pub fn cooldown(&self, character_id: u32, skill: &str) -> Option<u16> {
+        self.cooldowns
+            .iter()
+            .find(|x| x.character_id == character_id && x.skill == skill && x.ready_turn > self.turn + 1)
+            .map(|x| x.ready_turn)
+    }
+}
+
+impl Riddle {
+    /// Checks an answer, using up an attempt when it's wrong. Returns whether it was right.
+    pub fn answer(&mut self, guess: &str) -> Result<bool, ApiError> {
+        if self.status != RiddleStatus::Unsolved {
+            return Err(ApiError::BadRequest("The riddle has already been settled".to_string()));
+        }
+
+        let definition = riddles::find(&self.id).ok_or(ApiError::Internal(format!("riddle {} is missing", self.id)))?;
+
+        if definition.accepts(guess) {
+            self.status = RiddleStatus::Solved;
+            return Ok(true);
+        }
+
+        self.guesses.push(guess.trim().to_string());
+        self.attempts_left = self.attempts_left.saturating_sub(1);
+        if self.attempts_left == 0 {
+            self.status = RiddleStatus::Failed;
+        }
+
+        Ok(false)
+    }
+}
+
+impl Npc {
+    /// Opens a conversation at the start of `dialogue`
+    pub fn new(dialogue: &DialogueDefinition, level: u32, party_size: usize, seed: u64) -> Npc {
+        let mut npc = Npc {
+            dialogue: dialogue.id.clone(),
+            name: dialogue.npc.clone(),
+            level,
+            party_size,
+            seed,
+            ..Npc::default()
+        };
+
+        if let Some(node) = dialogue.node(&dialogue.start) {
+            npc.say(node);
+        }
+        npc
+    }
+
+    fn say(&mut self, node: &DialogueNode) {
+        self.node = node.id.clone();
+        self.text = node.text.clone();
+        self.choices = node.choices.iter().map(|x| x.text.clone()).collect();
+    }
+
+    fn end(&mut self, reward: EncounterReward) {
+        self.status = DialogueStatus::Over;
+        self.reward = reward;
+        self.text.clear();
+        self.choices.clear();
+    }
+
+    /// Responds with one of the choices, moving the conversation along. Returns where the response led,
+    /// what happens outside the conversation is up to the quest.
+    pub fn choose(&mut self, choice: usize) -> Result<&'static DialogueOutcome, ApiError> {
+        if self.status != DialogueStatus::Talking {
+            return Err(ApiError::BadRequest(format!("The conversation with {} is over", self.name)));
+        }
+
+        let dialogue = dialogues::find(&self.dialogue).ok_or(ApiError::Internal(format!("dialogue {} is missing", self.dialogue)))?;
+        let node = dialogue
+            .node(&self.node)
+            .ok_or(ApiError::Internal(format!("dialogue {} has no node {}", self.dialogue, self.node)))?;
+        let picked = node
+            .choices
+            .get(choice)
+            .ok_or(ApiError::BadRequest(format!("There is no response {choice}")))?;
+
+        self.transcript.push(format!("{}: {}", self.name, node.text));
+        self.transcript.push(format!("Party: {}", picked.text));
+
+        match &picked.outcome {
+            DialogueOutcome::Goto(next) => {
+                let next = dialogue
+                    .node(next)
+                    .ok_or(ApiError::Internal(format!("dialogue {} has no node {next}", self.dialogue)))?;
+                self.say(next);
+            }
+            DialogueOutcome::Reward(reward) => self.end(reward.scaled(self.level)),
+            // The buyer pays for a trade on the spot, the party as a whole gets nothing
+            DialogueOutcome::Trade { .. } | DialogueOutcome::Fight(_) | DialogueOutcome::Leave => self.end(EncounterReward::NoReward),
+        }
+
+        Ok(&picked.outcome)
+    }
+}
+
+impl Quest {
+    /// Moves the quest along its lifecycle, created quests start and started quests end
+    pub fn set_status(&mut self, status: QuestStatus) -> Result<(), ApiError> {
+        let allowed = matches!(
+            (self.status, status),
+            (QuestStatus::Created, QuestStatus::InProgress)
+                | (QuestStatus::Created, QuestStatus::Failed)
+                | (QuestStatus::InProgress, QuestStatus::Completed)
+                | (QuestStatus::InProgress, QuestStatus::Failed)
+        );
+
+        if !allowed {
+            return Err(ApiError::BadRequest(format!(
+                "A quest can't go from {:?} to {status:?}",
+                self.status
+            )));
+        }
+
+        self.status = status;
+        Ok(())
+    }
+
+    /// Closes the open encounter and opens the next one, starting the quest if needed.
+    /// The quest completes once its last encounter is closed.
+    pub fn advance(&mut self) -> Result<(), ApiError> {
+        match self.status {
+            QuestStatus::Created => {
+                self.set_status(QuestStatus::InProgress)?;
+                self.encounter_index = 0;
+                self.open_encounter = Some(self.encounters[0].clone());
+                return Ok(());
+            }
+            QuestStatus::InProgress => {}
+            _ => return Err(ApiError::BadRequest("The quest is already over".to_string())),
+        }
+
+        if self.ending.is_some() {
+            return Err(ApiError::BadRequest("The party is still rolling for loot".to_string()));
+        }
+
+        let fighting = matches!(
+            &self.open_encounter,
+            Some(Encounter::CombatEncounter(combat)) if combat.status != CombatStatus::Victory
+        );
+        if fighting {
+            return Err(ApiError::BadRequest("The party has to win the fight first".to_string()));
+        }
+
+        self.close_encounter();
+
+        self.encounter_index += 1;
+        match self.encounters.get(self.encounter_index) {
+            Some(next) => self.open_encounter = Some(next.clone()),
+            None => self.finish(QuestStatus::Completed)?,
+        }
+
+        Ok(())
+    }
+
+    /// Resolves the open encounter and hands its reward out to the members
+    fn close_encounter(&mut self) {
+        if let Some(encounter) = self.open_encounter.take() {
+            let outcome = encounter.resolve(self.encounter_index);
+            self.reward_members(&outcome.reward);
+
+            let mut items = outcome.loot.clone();
+            if let EncounterReward::ItemReward(item) = &outcome.reward {
+                items.push(item.clone());
+            }
+            self.hand_out(items);
+
+            self.history.push(outcome);
+        }
+    }
+
+    /// Shares a reward out according to the reward policy, recording every level reached
+    fn reward_members(&mut self, reward: &EncounterReward) {
+        let count = self.members.len();
+
+        for (i, member) in self.members.iter_mut().enumerate() {
+            let earned = self.reward_policy.share(reward.coins(), count, i);
+            let lost = self.reward_policy.share(reward.coin_penalty(), count, i);
+            member.coins = member.coins.saturating_add(earned).saturating_sub(lost);
+
+            if let Some(level_up) = member.gain_experience(self.reward_policy.share(reward.experience(), count, i)) {
+                self.level_ups.push(level_up);
+            }
+        }
+    }
+
+    /// Answers the riddle in the open encounter, returning whether the answer was right
+    pub fn answer_riddle(&mut self, guess: &str) -> Result<bool, ApiError> {
+        match &mut self.open_encounter {
+            Some(Encounter::RiddleEncounter(riddle)) if self.status == QuestStatus::InProgress => riddle.answer(guess),
+            _ => Err(ApiError::BadRequest("There is no riddle to answer".to_string())),
+        }
+    }
+
+    /// Responds to the stranger in the open encounter on behalf of `character_id`,
+    /// who pays for and gets whatever is bought. A conversation gone wrong turns the encounter into a fight.
+    pub fn respond(&mut self, character_id: u32, choice: usize) -> Result<(), ApiError> {
+        let npc = match &mut self.open_encounter {
+            Some(Encounter::NpcEncounter(npc)) if self.status == QuestStatus::InProgress => npc,
+            _ => return Err(ApiError::BadRequest("There is nobody to talk to".to_string())),
+        };
+
+        // The conversation only moves on if the response goes through, a buyer short of coins can pick again
+        let mut talk = npc.clone();
+        let outcome = talk.choose(choice)?;
+
+        if let DialogueOutcome::Trade { price, item } = outcome {
+            let member = self
+                .members
+                .iter_mut()
+                .find(|x| x.id == character_id)
+                .ok_or(ApiError::NotFound(format!("character {character_id} in the party")))?;
+
+            let cost = price * talk.level;
+            if cost > member.coins {
+                return Err(ApiError::BadRequest(format!(
+                    "{item} costs {cost} coins, {} only has {}",
+                    member.name, member.coins
+                )));
+            }
+
+            member.coins -= cost;
+            member.inventory.add(items::item(item));
+            talk.transcript.push(format!("{} pays {cost} coins for {item}", member.name));
+        }
+
+        if let DialogueOutcome::Fight(family) = outcome {
+            let mut combat = quest_gen::ambush(talk.seed, talk.level, talk.party_size, *family);
+            combat.log.push(CombatLogEntry {
+                turn: 0,
+                message: format!("The talk with {} turns into a fight", talk.name),
+            });
+            self.open_encounter = Some(Encounter::CombatEncounter(combat));
+        } else {
+            *npc = talk;
+        }
+
+        Ok(())
+    }
+
+    /// Ends the quest and sums up what the party earned on it. While drops are still being rolled for
+    /// the quest stays open, and it ends with `status` once the last of them is handed out.
+    pub fn finish(&mut self, status: QuestStatus) -> Result<(), ApiError> {
+        if self.status == QuestStatus::InProgress && self.loot_pending() {
+            self.open_encounter = None;
+            self.ending = Some(status);
+            return Ok(());
+        }
+
+        self.set_status(status)?;
+        self.open_encounter = None;
+        self.ending = None;
+
+        let earned: u32 = self.history.iter().map(|x| x.reward.coins()).sum();
+        let lost: u32 = self.history.iter().map(|x| x.reward.coin_penalty()).sum();
+
+        self.summary = Some(QuestSummary {
+            status,
+            encounters_cleared: self.history.len(),
+            coins: earned.saturating_sub(lost),
+            experience: self.history.iter().map(|x| x.reward.experience()).sum(),
+        });
+
+        Ok(())
+    }
+
+    /// Gives up on the quest. The members keep what they got so far, drops not everyone rolled for are settled.
+    pub fn abandon(&mut self) -> Result<(), ApiError> {
+        if self.status.is_finished() {
+            return Err(ApiError::BadRequest("The quest is already over".to_string()));
+        }
+
+        // Giving up while rolling for the last loot doesn't undo having made it through
+        self.settle_loot();
+        self.finish(self.ending.unwrap_or(QuestStatus::Failed))
+    }
+
+    /// Ends the quest in failure if the fight in the open encounter was lost
+    pub fn check_wipe(&mut self) -> Result<(), ApiError> {
+        let wiped = matches!(
+            &self.open_encounter,
+            Some(Encounter::CombatEncounter(combat)) if combat.status == CombatStatus::Wipe
+        );
+
+        if wiped {
+            self.close_encounter();
+            self.finish(QuestStatus::Failed)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether one of the members belongs to `user`
+    pub fn has_member(&self, user: u32) -> bool {
+        self.members.iter().any(|x| x.owner == user)
+    }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum CombatAction {
     WeaponAttack,
     UseItem(Item),
+    /// The name of one of the class skills
+    UseSkill(String),
 }
@@ -0,0 +1,119 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::bestiary::{self, FAMILIES, MonsterFamily};
+use crate::data::{MAX_COMBAT_ENEMIES, MAX_ENCOUNTER_LENGTH};
+use crate::dialogues;
+use crate::quest_data::{Combat, Encounter, EncounterReward, Npc, Riddle};
+use crate::riddles;
+
+/// Builds the encounters of a quest. The same seed and party level always gives the same quest,
+/// so a reported quest can be replayed from its seed.
+pub fn generate_encounters(seed: u64, party_level: u32, party_size: usize) -> [Encounter; MAX_ENCOUNTER_LENGTH] {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let level = party_level.max(1);
+
+    std::array::from_fn(|i| {
+        // Quests always end in a boss fight
+        if i == MAX_ENCOUNTER_LENGTH - 1 {
+            return combat_encounter(&mut rng, level, party_size, true);
+        }
+
+        match rng.random_range(0..10) {
+            0..4 => combat_encounter(&mut rng, level, party_size, false),
+            4..6 => npc_encounter(&mut rng, level, party_size),
+            6..8 => riddle_encounter(&mut rng, level),
+            _ => Encounter::EmptyEncounter,
+        }
+    })
+}
+
+fn combat_encounter(rng: &mut ChaCha8Rng, level: u32, party_size: usize, boss: bool) -> Encounter {
+    let family = FAMILIES[rng.random_range(0..FAMILIES.len())];
+    Encounter::CombatEncounter(fight(rng, family, level, party_size, boss))
+}
+
+/// The fight a stranger starts when a conversation goes wrong
+pub fn ambush(seed: u64, level: u32, party_size: usize, family: MonsterFamily) -> Combat {
+    fight(&mut ChaCha8Rng::seed_from_u64(seed), family, level, party_size, false)
+}
+
+/// A fight against monsters of a single family, led by one of its bosses if `boss` is set
+fn fight(rng: &mut ChaCha8Rng, family: MonsterFamily, level: u32, party_size: usize, boss: bool) -> Combat {
+    let count = rng.random_range(1..=(party_size + 1).min(MAX_COMBAT_ENEMIES));
+    let mut combat = Combat {
+        seed: rng.random(),
+        ..Combat::default()
+    };
+
+    if boss {
+        let bosses = bestiary::bosses(family);
+        combat.monsters.push(bosses[rng.random_range(0..bosses.len())].spawn(level));
+    }
+
+    let regulars = bestiary::regulars(family);
+    while combat.monsters.len() < count {
+        combat.monsters.push(regulars[rng.random_range(0..regulars.len())].spawn(level));
+    }
+
+    combat
+}
+
+/// A stranger with one of the conversations from `data/dialogues.json`
+fn npc_encounter(rng: &mut ChaCha8Rng, level: u32, party_size: usize) -> Encounter {
+    let dialogues = dialogues::all();
+    let dialogue = &dialogues[rng.random_range(0..dialogues.len())];
+
+    Encounter::NpcEncounter(Npc::new(dialogue, level, party_size, rng.random()))
+}
+
+/// One of the riddles from `data/riddles.json`, worth more than a stranger's gift but costly to get wrong
+fn riddle_encounter(rng: &mut ChaCha8Rng, level: u32) -> Encounter {
+    let riddles = riddles::all();
+    let definition = &riddles[rng.random_range(0..riddles.len())];
+
+    Encounter::RiddleEncounter(Riddle {
+        id: definition.id.clone(),
+        question: definition.question.clone(),
+        attempts_left: definition.attempts,
+        reward: EncounterReward::CoinAndExperienceReward(rng.random_range(10..=20) * level, rng.random_range(15..=30) * level),
+        penalty: EncounterReward::CoinPenalty(rng.random_range(3..=8) * level),
+        ..Riddle::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_the_same_quest() {
+        for seed in 0..50 {
+            let first = serde_json::to_string(&generate_encounters(seed, 3, 2)).unwrap();
+            let again = serde_json::to_string(&generate_encounters(seed, 3, 2)).unwrap();
+            assert_eq!(first, again, "seed {seed}");
+        }
+
+        let one = serde_json::to_string(&generate_encounters(1, 3, 2)).unwrap();
+        let other = serde_json::to_string(&generate_encounters(2, 3, 2)).unwrap();
+        assert_ne!(one, other);
+    }
+
+    #[test]
+    fn quests_end_in_a_boss_fight() {
+        for seed in 0..50 {
+            let encounters = generate_encounters(seed, 1, 1 + seed as usize % 3);
+            let combat = match &encounters[MAX_ENCOUNTER_LENGTH - 1] {
+                Encounter::CombatEncounter(combat) => combat,
+                other => panic!("seed {seed} ends with {}", other.name()),
+            };
+
+            let leader = &combat.monsters[0];
+            assert!(
+                bestiary::bosses(leader.family).iter().any(|x| x.name == leader.name),
+                "seed {seed} ends with {}",
+                leader.name
+            );
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game_data::{self, Authored};
+
+/// A riddle as written in `data/riddles.json`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RiddleDefinition {
+    pub id: String,
+    pub question: String,
+    pub answers: Vec<String>,
+    pub attempts: u8,
+}
+
+static RIDDLES: LazyLock<Result<Vec<RiddleDefinition>, String>> = LazyLock::new(|| game_data::load(include_str!("../data/riddles.json")));
+
+pub fn all() -> &'static [RiddleDefinition] {
+    game_data::all(&RIDDLES)
+}
+
+pub fn find(id: &str) -> Option<&'static RiddleDefinition> {
+    all().iter().find(|x| x.id == id)
+}
+
+/// Checks the riddles in `data/riddles.json` can all be asked and answered
+pub fn validate() -> Result<(), Vec<String>> {
+    game_data::validate(&RIDDLES)
+}
+
+impl Authored for RiddleDefinition {
+    const KIND: &'static str = "riddle";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.answers.is_empty() {
+            problems.push("there are no answers".to_string());
+        }
+        if self.attempts == 0 {
+            problems.push("it can't be attempted".to_string());
+        }
+
+        problems
+    }
+}
+
+impl RiddleDefinition {
+    /// Whether `guess` is one of the accepted answers, ignoring case and surrounding whitespace
+    pub fn accepts(&self, guess: &str) -> bool {
+        let guess = guess.trim().to_lowercase();
+        self.answers.iter().any(|x| x.to_lowercase() == guess)
+    }
+}
@@ -0,0 +1,269 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    Extension, Json, Router,
+    routing::{get, post},
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::AuthUser;
+use crate::data::{Character, Item, ItemKind, Rarity, SharedState};
+use crate::error::ApiError;
+use crate::items::{self, ItemTemplate};
+
+/// How many different items are for sale on a day
+pub const STOCK_SIZE: usize = 6;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// What the shop sells today
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Shop {
+    /// Days since the unix epoch, the stock changes with it
+    pub day: u64,
+    pub stock: Vec<Offer>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Offer {
+    pub item: Item,
+    pub price: u32,
+}
+
+/// What the client sends to buy or sell `quantity` of an item for one of their characters
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ShopOrder {
+    pub character_id: u32,
+    pub item: String,
+    #[serde(default = "one")]
+    pub quantity: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/shop", get(get_shop))
+        .route("/shop/buy", post(buy))
+        .route("/shop/sell", post(sell))
+}
+
+/// What a single `item` costs in the shop, rarer items and gear cost more
+pub fn price(item: &Item) -> u32 {
+    let base = match item.rarity {
+        Rarity::Common => 5,
+        Rarity::Uncommon => 15,
+        Rarity::Rare => 40,
+        Rarity::Epic => 100,
+    };
+
+    match item.kind {
+        ItemKind::Weapon | ItemKind::Armor | ItemKind::Trinket => base * 2,
+        ItemKind::Consumable | ItemKind::Misc => base,
+    }
+}
+
+/// What the shop pays for a single `item`, half its price
+pub fn sell_price(item: &Item) -> u32 {
+    (price(item) / 2).max(1)
+}
+
+pub fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs() / SECONDS_PER_DAY)
+}
+
+impl Shop {
+    /// The stock of `day`, the same day always gives the same stock.
+    /// Rare items are less likely to be on the shelves, and miscellany is never sold.
+    pub fn for_day(day: u64) -> Shop {
+        let mut rng = ChaCha8Rng::seed_from_u64(day);
+        let mut wares: Vec<&ItemTemplate> = items::ITEMS.iter().filter(|x| x.kind != ItemKind::Misc).collect();
+        let mut stock = Vec::new();
+
+        while stock.len() < STOCK_SIZE && !wares.is_empty() {
+            let index = rng.random_range(0..wares.len());
+            let chance = match wares[index].rarity {
+                Rarity::Common => 100,
+                Rarity::Uncommon => 60,
+                Rarity::Rare => 30,
+                Rarity::Epic => 10,
+            };
+
+            if rng.random_range(0..100) < chance {
+                let item = wares.remove(index).build(1);
+                stock.push(Offer {
+                    price: price(&item),
+                    item,
+                });
+            }
+        }
+
+        Shop { day, stock }
+    }
+
+    pub fn offer(&self, name: &str) -> Option<&Offer> {
+        self.stock.iter().find(|x| x.item.name == name)
+    }
+}
+
+impl Character {
+    /// Buys `quantity` of an item in stock, as long as the character can pay for it
+    pub fn buy(&mut self, shop: &Shop, name: &str, quantity: u32) -> Result<(), ApiError> {
+        let offer = shop
+            .offer(name)
+            .ok_or(ApiError::NotFound(format!("{name} in today's stock")))?;
+
+        let cost = offer.price.saturating_mul(quantity);
+        if cost > self.coins {
+            return Err(ApiError::BadRequest(format!(
+                "{quantity} {name} costs {cost} coins, {} only has {}",
+                self.name, self.coins
+            )));
+        }
+
+        self.coins -= cost;
+        self.inventory.add(Item {
+            quantity,
+            ..offer.item.clone()
+        });
+
+        Ok(())
+    }
+
+    /// Sells `quantity` of a carried item, worn gear has to be taken off first
+    pub fn sell(&mut self, name: &str, quantity: u32) -> Result<(), ApiError> {
+        let item = self
+            .inventory
+            .get(name)
+            .ok_or(ApiError::NotFound(format!("{name} in {}'s inventory", self.name)))?;
+
+        if item.quantity < quantity {
+            return Err(ApiError::BadRequest(format!("{} only carries {} {name}", self.name, item.quantity)));
+        }
+
+        let earned = sell_price(item).saturating_mul(quantity);
+        for _ in 0..quantity {
+            self.inventory.take(name);
+        }
+        self.coins = self.coins.saturating_add(earned);
+
+        Ok(())
+    }
+}
+
+async fn get_shop(_user: AuthUser) -> Json<Shop> {
+    Json(Shop::for_day(today()))
+}
+
+/// Buys for one of the caller's characters, paying from its coins
+async fn buy(Extension(state): Extension<SharedState>, user: AuthUser, Json(order): Json<ShopOrder>) -> Result<Json<Character>, ApiError> {
+    trade(state, user, order, |chr, order| chr.buy(&Shop::for_day(today()), &order.item, order.quantity))
+}
+
+/// Sells from the inventory of one of the caller's characters
+async fn sell(Extension(state): Extension<SharedState>, user: AuthUser, Json(order): Json<ShopOrder>) -> Result<Json<Character>, ApiError> {
+    trade(state, user, order, |chr, order| chr.sell(&order.item, order.quantity))
+}
+
+/// Applies a purchase or sale to a copy of the character, which is only saved and kept if it went through
+fn trade(
+    state: SharedState,
+    user: AuthUser,
+    order: ShopOrder,
+    deal: impl FnOnce(&mut Character, &ShopOrder) -> Result<(), ApiError>,
+) -> Result<Json<Character>, ApiError> {
+    if order.quantity == 0 {
+        return Err(ApiError::BadRequest("Trade at least one item".to_string()));
+    }
+
+    let mut state = state.write().unwrap();
+    let id = order.character_id;
+
+    let mut chr = state.rested_character(user.id, id)?;
+    if state.on_quest(id) {
+        return Err(ApiError::BadRequest(format!("{} is away on a quest", chr.name)));
+    }
+
+    deal(&mut chr, &order)?;
+
+    state.storage.save_character(id, &chr)?;
+    state.characters.insert(id, chr.clone());
+
+    Ok(Json(chr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::CharacterClass;
+
+    fn carried(chr: &Character, name: &str) -> u32 {
+        chr.inventory.get(name).map_or(0, |x| x.quantity)
+    }
+
+    #[test]
+    fn rarer_items_and_gear_cost_more() {
+        let potion = Item {
+            kind: ItemKind::Consumable,
+            ..Item::default()
+        };
+        let sword = Item {
+            kind: ItemKind::Weapon,
+            rarity: Rarity::Rare,
+            ..Item::default()
+        };
+
+        assert_eq!((price(&potion), sell_price(&potion)), (5, 2));
+        assert_eq!((price(&sword), sell_price(&sword)), (80, 40));
+    }
+
+    #[test]
+    fn buying_and_selling_trade_coins_for_items() {
+        let shop = Shop::for_day(1);
+        let offer = &shop.stock[0];
+        let name = offer.item.name.as_str();
+        let mut chr = Character::new(1, 1, "Al".to_string(), CharacterClass::Rogue);
+        let before = carried(&chr, name);
+        chr.coins = offer.price * 3;
+
+        chr.buy(&shop, name, 2).unwrap();
+        assert_eq!(chr.coins, offer.price);
+        assert_eq!(carried(&chr, name), before + 2);
+
+        chr.sell(name, 1).unwrap();
+        assert_eq!(chr.coins, offer.price + sell_price(&offer.item));
+        assert_eq!(carried(&chr, name), before + 1);
+    }
+
+    #[test]
+    fn trades_are_refused_without_the_coins_or_the_items() {
+        let shop = Shop::for_day(1);
+        let offer = &shop.stock[0];
+        let name = offer.item.name.as_str();
+        let mut chr = Character::new(1, 1, "Al".to_string(), CharacterClass::Rogue);
+        chr.coins = offer.price - 1;
+        let before = carried(&chr, name);
+
+        assert!(chr.buy(&shop, name, 1).is_err());
+        assert_eq!(chr.coins, offer.price - 1);
+        assert_eq!(carried(&chr, name), before);
+
+        assert!(chr.sell("Dragon Egg", 1).is_err());
+        assert!(chr.sell(name, before + 1).is_err());
+        assert_eq!(chr.coins, offer.price - 1);
+    }
+
+    #[test]
+    fn the_same_day_has_the_same_stock() {
+        for day in 0..20 {
+            let stock = serde_json::to_string(&Shop::for_day(day)).unwrap();
+            assert_eq!(stock, serde_json::to_string(&Shop::for_day(day)).unwrap(), "day {day}");
+            assert_eq!(Shop::for_day(day).stock.len(), STOCK_SIZE);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use crate::data::{CharacterClass, Stats};
+use crate::status::{StatusKind, StatusSpec};
+
+/// An ability a class can use in combat, paid for with energy
+#[derive(Debug)]
+pub struct Skill {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub energy: i32,
+    /// Turns to wait after using the skill before it can be used again
+    pub cooldown: u16,
+    pub target: SkillTarget,
+    pub effect: SkillEffect,
+}
+
+/// Who a skill lands on. `Enemy` aims at `TurnAction.target` among the monsters,
+/// `Ally` at the party member whose character id is `TurnAction.target`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkillTarget {
+    Enemy,
+    AllEnemies,
+    Ally,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SkillEffect {
+    /// Damage on top of the user's attack
+    Damage(i32),
+    Heal(i32),
+    /// Raises the combat stats of the target until the fight is over
+    Buff(Stats),
+    Status(StatusSpec),
+}
+
+const fn buff(attack: i32, defense: i32, evasion: i32) -> SkillEffect {
+    SkillEffect::Buff(Stats {
+        health: 0,
+        energy: 0,
+        attack,
+        defense,
+        speed: 0,
+        crit: 0,
+        evasion,
+    })
+}
+
+const fn status(kind: StatusKind, potency: i32, turns: u16) -> SkillEffect {
+    SkillEffect::Status(StatusSpec { kind, potency, turns })
+}
+
+const WARRIOR_SKILLS: &[Skill] = &[
+    Skill {
+        name: "Heavy Blow",
+        description: "A slow swing that hits hard",
+        energy: 2,
+        cooldown: 1,
+        target: SkillTarget::Enemy,
+        effect: SkillEffect::Damage(4),
+    },
+    Skill {
+        name: "Cleave",
+        description: "Sweeps through every monster",
+        energy: 3,
+        cooldown: 2,
+        target: SkillTarget::AllEnemies,
+        effect: SkillEffect::Damage(1),
+    },
+    Skill {
+        name: "Shield Wall",
+        description: "Covers an ally, raising their defense by 2",
+        energy: 2,
+        cooldown: 3,
+        target: SkillTarget::Ally,
+        effect: buff(0, 2, 0),
+    },
+    Skill {
+        name: "Shield Bash",
+        description: "Stuns a monster, making it lose its next action",
+        energy: 3,
+        cooldown: 3,
+        target: SkillTarget::Enemy,
+        effect: status(StatusKind::Stun, 0, 2),
+    },
+    Skill {
+        name: "Second Wind",
+        description: "An ally regains 2 health every turn for 3 turns",
+        energy: 2,
+        cooldown: 4,
+        target: SkillTarget::Ally,
+        effect: status(StatusKind::Regen, 2, 3),
+    },
+];
+
+const MAGE_SKILLS: &[Skill] = &[
+    Skill {
+        name: "Firebolt",
+        description: "A bolt of flame at a single monster",
+        energy: 2,
+        cooldown: 0,
+        target: SkillTarget::Enemy,
+        effect: SkillEffect::Damage(4),
+    },
+    Skill {
+        name: "Frost Nova",
+        description: "A burst of cold that hits every monster",
+        energy: 4,
+        cooldown: 2,
+        target: SkillTarget::AllEnemies,
+        effect: SkillEffect::Damage(2),
+    },
+    Skill {
+        name: "Mend",
+        description: "Heals an ally for 6 health",
+        energy: 3,
+        cooldown: 1,
+        target: SkillTarget::Ally,
+        effect: SkillEffect::Heal(6),
+    },
+    Skill {
+        name: "Barrier",
+        description: "Shields an ally from the next 8 damage for 3 turns",
+        energy: 3,
+        cooldown: 3,
+        target: SkillTarget::Ally,
+        effect: status(StatusKind::Shield, 8, 3),
+    },
+];
+
+const ROGUE_SKILLS: &[Skill] = &[
+    Skill {
+        name: "Backstab",
+        description: "A quick stab where it hurts",
+        energy: 2,
+        cooldown: 1,
+        target: SkillTarget::Enemy,
+        effect: SkillEffect::Damage(3),
+    },
+    Skill {
+        name: "Fan of Knives",
+        description: "Throws knives at every monster",
+        energy: 3,
+        cooldown: 2,
+        target: SkillTarget::AllEnemies,
+        effect: SkillEffect::Damage(1),
+    },
+    Skill {
+        name: "Smoke Bomb",
+        description: "Hides an ally, raising their evasion by 20",
+        energy: 3,
+        cooldown: 3,
+        target: SkillTarget::Ally,
+        effect: buff(0, 0, 20),
+    },
+    Skill {
+        name: "Envenom",
+        description: "Poisons a monster for 2 damage a turn over 3 turns",
+        energy: 2,
+        cooldown: 2,
+        target: SkillTarget::Enemy,
+        effect: status(StatusKind::Poison, 2, 3),
+    },
+];
+
+impl CharacterClass {
+    pub fn skills(&self) -> &'static [Skill] {
+        match self {
+            CharacterClass::Warrior => WARRIOR_SKILLS,
+            CharacterClass::Mage => MAGE_SKILLS,
+            CharacterClass::Rogue => ROGUE_SKILLS,
+        }
+    }
+
+    pub fn skill(&self, name: &str) -> Option<&'static Skill> {
+        self.skills().iter().find(|x| x.name == name)
+    }
+}
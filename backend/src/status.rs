@@ -0,0 +1,71 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusKind {
+    /// Loses `potency` health at the end of every turn
+    Poison,
+    /// Skips their next action
+    Stun,
+    /// Absorbs up to `potency` damage
+    Shield,
+    /// Recovers `potency` health at the end of every turn
+    Regen,
+}
+
+/// A status effect as a skill or monster hands it out
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusSpec {
+    pub kind: StatusKind,
+    pub potency: i32,
+    pub turns: u16,
+}
+
+/// Someone in a fight, a party member by character id or a monster by its index in `Combat.monsters`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Combatant {
+    Member(u32),
+    Monster(usize),
+}
+
+/// A status effect on someone in a fight, it wears off after `turns` more turn ends
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub target: Combatant,
+    pub kind: StatusKind,
+    pub potency: i32,
+    pub turns: u16,
+}
+
+impl StatusSpec {
+    pub fn on(self, target: Combatant) -> StatusEffect {
+        StatusEffect {
+            target,
+            kind: self.kind,
+            potency: self.potency,
+            turns: self.turns,
+        }
+    }
+}
+
+impl StatusEffect {
+    /// A short tag to show next to the affected unit
+    pub fn label(&self) -> String {
+        match self.kind {
+            StatusKind::Stun => format!("{} {}t", self.kind, self.turns),
+            _ => format!("{} {} {}t", self.kind, self.potency, self.turns),
+        }
+    }
+}
+
+impl fmt::Display for StatusKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatusKind::Poison => write!(f, "Poisoned"),
+            StatusKind::Stun => write!(f, "Stunned"),
+            StatusKind::Shield => write!(f, "Shielded"),
+            StatusKind::Regen => write!(f, "Regenerating"),
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt;
+use std::sync::Mutex;
+
+use rusqlite::{Connection, params};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::data::{Character, User};
+use crate::quest_data::Quest;
+
+pub const DEFAULT_DB_PATH: &str = "ratventures.db";
+
+const USERS: &str = "users";
+const PASSWORDS: &str = "passwords";
+const CHARACTERS: &str = "characters";
+const QUESTS: &str = "quests";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS users (
+        id INTEGER PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS passwords (
+        id INTEGER PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS characters (
+        id INTEGER PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS quests (
+        id INTEGER PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+";
+
+#[derive(Debug)]
+pub enum StorageError {
+    Sqlite(rusqlite::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Sqlite(e) => write!(f, "sqlite error: {e}"),
+            StorageError::Json(e) => write!(f, "serialization error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(e: rusqlite::Error) -> Self {
+        StorageError::Sqlite(e)
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(e: serde_json::Error) -> Self {
+        StorageError::Json(e)
+    }
+}
+
+/// SQLite backed persistence for users, characters and quests.
+///
+/// Every row is stored as its JSON representation next to its id, so adding
+/// fields to the data types doesn't need a schema migration.
+#[derive(Debug)]
+pub struct Storage {
+    conn: Mutex<Connection>,
+}
+
+impl Storage {
+    /// Opens (or creates) the database at `path` and makes sure the schema exists
+    pub fn open(path: &str) -> Result<Storage, StorageError> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Storage, StorageError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Storage, StorageError> {
+        conn.execute_batch(SCHEMA)?;
+
+        Ok(Storage {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn save_user(&self, id: u32, user: &User) -> Result<(), StorageError> {
+        self.save(USERS, id, user)
+    }
+
+    pub fn load_users(&self) -> Result<Vec<(u32, User)>, StorageError> {
+        self.load(USERS)
+    }
+
+    /// Saves the password hash of the user with `id`
+    pub fn save_password(&self, id: u32, hash: &str) -> Result<(), StorageError> {
+        self.save(PASSWORDS, id, hash)
+    }
+
+    /// Saves a new user together with its password hash, either both are saved or neither is
+    pub fn save_account(&self, id: u32, user: &User, hash: &str) -> Result<(), StorageError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        Self::insert(&tx, USERS, id, user)?;
+        Self::insert(&tx, PASSWORDS, id, hash)?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn load_passwords(&self) -> Result<Vec<(u32, String)>, StorageError> {
+        self.load(PASSWORDS)
+    }
+
+    pub fn save_character(&self, id: u32, character: &Character) -> Result<(), StorageError> {
+        self.save(CHARACTERS, id, character)
+    }
+
+    pub fn load_characters(&self) -> Result<Vec<(u32, Character)>, StorageError> {
+        self.load(CHARACTERS)
+    }
+
+    pub fn save_quest(&self, id: u32, quest: &Quest) -> Result<(), StorageError> {
+        self.save(QUESTS, id, quest)
+    }
+
+    pub fn load_quests(&self) -> Result<Vec<(u32, Quest)>, StorageError> {
+        self.load(QUESTS)
+    }
+
+    fn save<T: Serialize + ?Sized>(&self, table: &str, id: u32, value: &T) -> Result<(), StorageError> {
+        Self::insert(&self.conn.lock().unwrap(), table, id, value)
+    }
+
+    fn insert<T: Serialize + ?Sized>(conn: &Connection, table: &str, id: u32, value: &T) -> Result<(), StorageError> {
+        let data = serde_json::to_string(value)?;
+
+        conn.execute(
+            &format!("INSERT OR REPLACE INTO {table} (id, data) VALUES (?1, ?2)"),
+            params![id, data],
+        )?;
+
+        Ok(())
+    }
+
+    fn load<T: DeserializeOwned>(&self, table: &str) -> Result<Vec<(u32, T)>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT id, data FROM {table} ORDER BY id"))?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut result = vec![];
+        for row in rows {
+            let (id, data) = row?;
+            result.push((id, serde_json::from_str(&data)?));
+        }
+
+        Ok(result)
+    }
+}
@@ -1,65 +1,252 @@
-use std::{collections::HashMap, error::Error};
+use std::error::Error;
 
-use ratback::{data::Character, data::User, quest_data::Quest};
-use reqwest::blocking::Client;
+use ratback::{
+    data::Character, data::Credentials, inn::InnStay, data::EquipSlot, data::Equip, data::Unequip, data::JoinParty, data::NewCharacter, data::Party, data::PartyInvite,
+    data::PartyLobby, data::Session, data::User, error::ApiError, quest_data::CombatAction, quest_data::CombatRequest, quest_data::DialogueChoice, quest_data::LootChoice, quest_data::NewQuest, quest_data::RollChoice, quest_data::Quest, quest_data::RiddleAnswer,
+    shop::Shop, shop::ShopOrder,
+};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
 
 const HOST: &str = "http://localhost:3000/api/";
 
 #[derive(Debug, Default)]
 pub struct Rattp {
     pub http: Client,
+    token: Option<String>,
 }
 
 impl Rattp {
 
     fn destination(path: &str) -> String {
         let mut destination = HOST.to_string();
-        destination.push_str(&path);
+        destination.push_str(path);
 
         destination
     }
 
+    /// A POST request to `path`, carrying the session token once logged in
+    fn post(&self, path: &str) -> RequestBuilder {
+        self.authorize(self.http.post(Self::destination(path)))
+    }
+
+    /// A GET request to `path`, carrying the session token once logged in
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.authorize(self.http.get(Self::destination(path)))
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Deserializes a successful response as `T`, or the `ApiError` the server answered with.
+    /// Requests rejected before reaching a handler, like a malformed body, get a plain text answer instead.
+    fn parse<T: DeserializeOwned>(response: Response) -> Result<T, Box<dyn Error>> {
+        let status = response.status();
+        let body = response.text()?;
+
+        if status.is_success() {
+            return Ok(serde_json::from_str(&body)?);
+        }
+
+        match serde_json::from_str::<ApiError>(&body) {
+            Ok(err) => Err(Box::new(err)),
+            Err(_) => Err(format!("{status}: {}", body.trim()).into()),
+        }
+    }
+
+    #[allow(dead_code)]
     pub fn get_hello(&self) -> Result<String, Box<dyn Error>> {
         let response: String = self.http.get(Self::destination("hello-world")).send()?.text()?;
 
         Ok(response)
     }
 
-    pub fn post_register_user(&self, username: String) -> Result<User, Box<dyn Error>> {
-        let response = self.http.post(Self::destination("register")).body(username).send()?.text()?;
+    pub fn post_register_user(&self, credentials: &Credentials) -> Result<User, Box<dyn Error>> {
+        let response = self.post("register").json(credentials).send()?;
+
+        Self::parse(response)
+    }
+
+    /// Logs in and keeps the session token for every following request
+    pub fn post_login(&mut self, credentials: &Credentials) -> Result<Session, Box<dyn Error>> {
+        let response = self.post("login").json(credentials).send()?;
+        let session: Session = Self::parse(response)?;
+
+        self.token = Some(session.token.clone());
+
+        Ok(session)
+    }
+
+    pub fn post_new_character(&self, new_character: &NewCharacter) -> Result<Character, Box<dyn Error>> {
+        let response = self.post("character").json(new_character).send()?;
+
+        Self::parse(response)
+    }
+
+    pub fn get_characters(&self) -> Result<Vec<Character>, Box<dyn Error>> {
+        let response = self.get("characters").send()?;
+
+        Self::parse(response)
+    }
+
+    pub fn post_equip(&self, character_id: u32, item: String) -> Result<Character, Box<dyn Error>> {
+        let response = self
+            .post(&format!("character/{character_id}/equip"))
+            .json(&Equip { item })
+            .send()?;
+
+        Self::parse(response)
+    }
+
+    pub fn get_shop(&self) -> Result<Shop, Box<dyn Error>> {
+        let response = self.get("shop").send()?;
+
+        Self::parse(response)
+    }
+
+    pub fn post_buy(&self, character_id: u32, item: String) -> Result<Character, Box<dyn Error>> {
+        let response = self
+            .post("shop/buy")
+            .json(&ShopOrder { character_id, item, quantity: 1 })
+            .send()?;
+
+        Self::parse(response)
+    }
+
+    pub fn post_sell(&self, character_id: u32, item: String) -> Result<Character, Box<dyn Error>> {
+        let response = self
+            .post("shop/sell")
+            .json(&ShopOrder { character_id, item, quantity: 1 })
+            .send()?;
+
+        Self::parse(response)
+    }
+
+    pub fn post_rest(&self, character_id: u32) -> Result<Character, Box<dyn Error>> {
+        let response = self.post("inn/rest").json(&InnStay { character_id }).send()?;
 
-        let usr: User = serde_json::from_str(&response)?;
+        Self::parse(response)
+    }
+
+    pub fn post_unequip(&self, character_id: u32, slot: EquipSlot) -> Result<Character, Box<dyn Error>> {
+        let response = self
+            .post(&format!("character/{character_id}/unequip"))
+            .json(&Unequip { slot })
+            .send()?;
+
+        Self::parse(response)
+    }
+
+    pub fn get_party(&self) -> Result<PartyLobby, Box<dyn Error>> {
+        let response = self.get("party").send()?;
+
+        Self::parse(response)
+    }
+
+    pub fn post_new_party(&self, character_id: u32) -> Result<Party, Box<dyn Error>> {
+        let response = self.post("party").json(&JoinParty { character_id }).send()?;
+
+        Self::parse(response)
+    }
+
+    pub fn post_party_invite(&self, username: String) -> Result<Party, Box<dyn Error>> {
+        let response = self.post("party/invite").json(&PartyInvite { username }).send()?;
+
+        Self::parse(response)
+    }
+
+    pub fn post_accept_invite(&self, party_id: u32, character_id: u32) -> Result<Party, Box<dyn Error>> {
+        let response = self
+            .post(&format!("party/{party_id}/accept"))
+            .json(&JoinParty { character_id })
+            .send()?;
 
-        Ok(usr)
+        Self::parse(response)
     }
 
-    pub fn post_new_character(&self) -> Result<Character, Box<dyn Error>> {
+    pub fn post_leave_party(&self) -> Result<PartyLobby, Box<dyn Error>> {
+        let response = self.post("party/leave").send()?;
 
-        let response = self.http.post(Self::destination("character")).send()?.text()?;
+        Self::parse(response)
+    }
+
+    pub(crate) fn post_new_quest(&self, seed: Option<u64>) -> Result<Quest, Box<dyn Error>> {
+        let response = self.post("quest").json(&NewQuest { seed }).send()?;
+
+        Self::parse(response)
+    }
 
-        let character: Character = serde_json::from_str(&response)?;
+    pub fn get_quest(&self, id: u32) -> Result<Quest, Box<dyn Error>> {
+        let response = self.get(&format!("quest/{id}")).send()?;
 
-        Ok(character)
+        Self::parse(response)
     }
-    
-    pub(crate) fn post_new_quest(&self) -> Result<Quest, Box<dyn Error>> {
-        let response = self.http.post(Self::destination("quest")).send()?.text()?;
 
-        let quest: Quest = serde_json::from_str(&response)?;
+    /// Every quest the caller's characters went on, oldest first
+    pub fn get_quests(&self) -> Result<Vec<Quest>, Box<dyn Error>> {
+        let response = self.get("quests").send()?;
 
-        Ok(quest)
+        Self::parse(response)
     }
 
-    /*
-    
-    pub(crate) fn post_new<'a, T>(&self, typ: &'a T, path: String) -> Result<T, Box<dyn Error>> 
-        where T: serde::Deserialize<'a>
-    {
-        let response = self.http.post(Self::destination(&path)).send()?.text()?;
+    pub fn post_combat_action(&self, quest_id: u32, action: CombatAction, target: usize) -> Result<Quest, Box<dyn Error>> {
+        let request = CombatRequest {
+            quest_id,
+            action,
+            target,
+        };
+        let response = self.post("combat").json(&request).send()?;
+
+        Self::parse(response)
+    }
+
+    pub fn post_riddle_answer(&self, id: u32, answer: String) -> Result<Quest, Box<dyn Error>> {
+        let response = self
+            .post(&format!("quest/{id}/answer"))
+            .json(&RiddleAnswer { answer })
+            .send()?;
+
+        Self::parse(response)
+    }
+
+    pub fn post_npc_response(&self, id: u32, choice: usize) -> Result<Quest, Box<dyn Error>> {
+        let response = self
+            .post(&format!("quest/{id}/respond"))
+            .json(&DialogueChoice { choice })
+            .send()?;
+
+        Self::parse(response)
+    }
+
+    pub fn post_loot_roll(&self, id: u32, drop: usize, choice: RollChoice) -> Result<Quest, Box<dyn Error>> {
+        let response = self
+            .post(&format!("quest/{id}/loot"))
+            .json(&LootChoice { drop, choice })
+            .send()?;
+
+        Self::parse(response)
+    }
+
+    /// Ends the turn for the party members who haven't chosen an action yet
+    pub fn post_end_turn(&self, id: u32) -> Result<Quest, Box<dyn Error>> {
+        let response = self.post(&format!("quest/{id}/end-turn")).send()?;
+
+        Self::parse(response)
+    }
+
+    pub fn post_advance_quest(&self, id: u32) -> Result<Quest, Box<dyn Error>> {
+        let response = self.post(&format!("quest/{id}/advance")).send()?;
+
+        Self::parse(response)
+    }
 
-        let quest: T = serde_json::from_str(&response)?;
+    pub fn post_abandon_quest(&self, id: u32) -> Result<Quest, Box<dyn Error>> {
+        let response = self.post(&format!("quest/{id}/abandon")).send()?;
 
-        Ok(quest)
+        Self::parse(response)
     }
-     */
 }
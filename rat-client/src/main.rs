@@ -1,6 +1,8 @@
+use std::error::Error;
+
 use color_eyre::{
     Result,
-    eyre::{WrapErr, eyre},
+    eyre::WrapErr,
 };
 use ratatui::{
     Frame,
@@ -10,19 +12,24 @@ use ratatui::{
     style::{Color, Modifier, Style, Stylize},
     symbols::border,
     text::{Line, Span, Text},
-    widgets::{Block, Borders, Paragraph, Widget},
+    widgets::{Block, Borders, Paragraph, Widget, Wrap},
 };
 
 extern crate ratback;
-use ratback::{data::Character, data::User, quest_data::Quest};
+use ratback::{
+    data::Character, data::CharacterClass, data::Credentials, data::MAX_PARTY_SIZE, data::NewCharacter,
+    data::PartyLobby, data::User, data::MAX_COMBAT_ENEMIES, data::Item, data::ItemKind, data::EquipSlot, data::Rarity,
+    shop::{self, Shop},
+    skills::SkillTarget,
+    status::Combatant,
+    quest_data::{Combat, CombatAction, CombatStatus, DialogueStatus, Encounter, LootStatus, Npc, Quest, RiddleStatus, RollChoice},
+};
 
 use crate::client::Rattp;
 
 mod client;
 mod tui;
 
-const MOB_HP: u8 = 5;
-
 fn main() -> Result<()> {
     color_eyre::install()?;
     let mut terminal = tui::init()?;
@@ -42,26 +49,52 @@ pub struct App {
     state: AppState,
     active_user: Option<User>,
     active_character: Option<Character>,
+    characters: Vec<Character>,
+    lobby: PartyLobby,
+    combat_target: usize,
+    /// Index into the loot pool of the drop to roll for
+    loot_selected: usize,
+    shop: Option<Shop>,
     active_quest: Option<Quest>,
     text_input: Option<String>,
+    pending_username: Option<String>,
+    pending_character_name: Option<String>,
+    error: Option<String>,
     client: Rattp,
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Copy, Debug, Default)]
 pub enum AppState {
     #[default]
     Main,
     TextInput(Reason),
     FinishInput(Reason),
+    CharacterSelect,
+    ClassSelect,
     Party,
     Combat,
+    ItemSelect,
+    SkillSelect,
+    /// Picking who receives the class skill at this index
+    AllySelect(usize),
+    Gear,
+    Dialogue,
+    Loot,
+    Shop,
+    /// The shop, showing what the active character can sell
+    Sell,
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub enum Reason {
     #[default]
     Register,
+    RegisterPassword,
+    Login,
+    LoginPassword,
     CreateCharacter,
+    Invite,
+    RiddleAnswer,
 }
 
 impl App {
@@ -92,20 +125,117 @@ impl App {
 
     fn handle_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
         match self.state {
-            AppState::TextInput(_) => match key_event.code {
-                KeyCode::Enter => self.finish_register_user(),
-                KeyCode::Char(value) => match self.text_input.as_mut() {
-                    Some(current) => {
+            AppState::TextInput(reason) => match key_event.code {
+                KeyCode::Enter => self.finish_text_input(reason),
+                KeyCode::Char(value) => {
+                    if let Some(current) = self.text_input.as_mut() {
                         current.push(value);
                     }
-                    _ => {}
-                },
-                KeyCode::Backspace => match self.text_input.as_mut() {
-                    Some(current) => {
+                }
+                KeyCode::Backspace => {
+                    if let Some(current) = self.text_input.as_mut() {
                         current.pop();
                     }
-                    _ => {}
-                },
+                }
+                KeyCode::Esc => {
+                    self.state = match reason {
+                        Reason::Invite => AppState::Party,
+                        _ => AppState::Main,
+                    }
+                }
+                _ => {}
+            },
+
+            AppState::ClassSelect => match key_event.code {
+                KeyCode::Char(value) if value.is_ascii_digit() => self.finish_character(value),
+                KeyCode::Esc => self.state = AppState::Main,
+                _ => {}
+            },
+
+            AppState::Party => match key_event.code {
+                KeyCode::Char('n') => self.create_party(),
+                KeyCode::Char('i') => self.start_text_input(Reason::Invite),
+                KeyCode::Char('l') => self.leave_party(),
+                KeyCode::Char('r') => self.refresh_party(),
+                KeyCode::Char('a') => self.start_quest(),
+                KeyCode::Char(value) if value.is_ascii_digit() => self.accept_invite(value),
+                KeyCode::Esc => self.state = AppState::Main,
+                _ => {}
+            },
+
+            AppState::Combat => match key_event.code {
+                KeyCode::Char('a') => self.combat_action(CombatAction::WeaponAttack),
+                KeyCode::Char('u') => self.state = AppState::ItemSelect,
+                KeyCode::Char('k') => self.state = AppState::SkillSelect,
+                KeyCode::Char('e') => self.end_turn(),
+                KeyCode::Char('r') => self.refresh_quest(),
+                KeyCode::Char('n') => self.advance_quest(),
+                KeyCode::Char(value) if value.is_ascii_digit() => self.select_target(value),
+                KeyCode::Esc => self.state = AppState::Main,
+                _ => {}
+            },
+
+            AppState::SkillSelect => match key_event.code {
+                KeyCode::Char(value) if value.is_ascii_digit() => self.use_skill(value),
+                KeyCode::Esc => self.state = AppState::Combat,
+                _ => {}
+            },
+
+            AppState::AllySelect(skill) => match key_event.code {
+                KeyCode::Char(value) if value.is_ascii_digit() => self.use_skill_on_ally(skill, value),
+                KeyCode::Esc => self.state = AppState::SkillSelect,
+                _ => {}
+            },
+
+            AppState::ItemSelect => match key_event.code {
+                KeyCode::Char(value) if value.is_ascii_digit() => self.use_item(value),
+                KeyCode::Esc => self.state = AppState::Combat,
+                _ => {}
+            },
+
+            AppState::Gear => match key_event.code {
+                KeyCode::Char('w') => self.unequip(EquipSlot::Weapon),
+                KeyCode::Char('a') => self.unequip(EquipSlot::Armor),
+                KeyCode::Char('t') => self.unequip(EquipSlot::Trinket),
+                KeyCode::Char(value) if value.is_ascii_digit() => self.equip(value),
+                KeyCode::Esc => self.state = AppState::Main,
+                _ => {}
+            },
+
+            AppState::Dialogue => match key_event.code {
+                KeyCode::Char(value) if value.is_ascii_digit() => self.respond_to_npc(value),
+                KeyCode::Char('r') => self.refresh_quest(),
+                KeyCode::Esc => self.state = AppState::Main,
+                _ => {}
+            },
+
+            AppState::Loot => match key_event.code {
+                KeyCode::Char('n') => self.roll_for_loot(RollChoice::Need),
+                KeyCode::Char('g') => self.roll_for_loot(RollChoice::Greed),
+                KeyCode::Char('p') => self.roll_for_loot(RollChoice::Pass),
+                KeyCode::Char('r') => self.refresh_quest(),
+                KeyCode::Char(value) if value.is_ascii_digit() => self.select_drop(value),
+                KeyCode::Esc => self.state = AppState::Main,
+                _ => {}
+            },
+
+            AppState::Shop => match key_event.code {
+                KeyCode::Char(value) if value.is_ascii_digit() => self.buy(value),
+                KeyCode::Char('s') => self.state = AppState::Sell,
+                KeyCode::Char('r') => self.start_shop(),
+                KeyCode::Esc => self.state = AppState::Main,
+                _ => {}
+            },
+
+            AppState::Sell => match key_event.code {
+                KeyCode::Char(value) if value.is_ascii_digit() => self.sell(value),
+                KeyCode::Char('b') => self.state = AppState::Shop,
+                KeyCode::Esc => self.state = AppState::Main,
+                _ => {}
+            },
+
+            AppState::CharacterSelect => match key_event.code {
+                KeyCode::Char(value) if value.is_ascii_digit() => self.select_character(value),
                 KeyCode::Esc => self.state = AppState::Main,
                 _ => {}
             },
@@ -113,8 +243,20 @@ impl App {
             _ => match key_event.code {
                 KeyCode::Char('q') => self.exit(),
                 KeyCode::Char('r') => self.start_register_user(),
-                KeyCode::Char('c') => self.register_character(),
+                KeyCode::Char('l') => self.start_login(),
+                KeyCode::Char('c') => self.start_character(),
+                KeyCode::Char('s') => self.start_character_select(),
+                KeyCode::Char('p') => self.start_party(),
+                KeyCode::Char('g') => self.start_gear(),
+                KeyCode::Char('f') => self.refresh_quest(),
+                KeyCode::Char('n') => self.advance_quest(),
+                KeyCode::Char('x') => self.abandon_quest(),
                 KeyCode::Char('a') => self.start_quest(),
+                KeyCode::Char('y') => self.start_riddle_answer(),
+                KeyCode::Char('t') => self.start_dialogue(),
+                KeyCode::Char('o') => self.start_loot(),
+                KeyCode::Char('h') => self.start_shop(),
+                KeyCode::Char('i') => self.rest(),
                 _ => {}
             },
         }
@@ -122,23 +264,61 @@ impl App {
         Ok(())
     }
 
+    /// Keeps the value of a request, or shows its error and returns `None`
+    fn report<T>(&mut self, result: Result<T, Box<dyn Error>>) -> Option<T> {
+        match result {
+            Ok(x) => {
+                self.error = None;
+                Some(x)
+            }
+            Err(e) => {
+                self.error = Some(e.to_string());
+                None
+            }
+        }
+    }
+
+    /// The list index of a pressed number key, `1` being the first entry
+    fn key_index(key: char) -> Option<usize> {
+        key.to_digit(10).and_then(|x| (x as usize).checked_sub(1))
+    }
+
     fn get_and_clear_text_input(&mut self) -> Option<String> {
         let value = self.text_input.clone();
         self.text_input = None;
         value
     }
 
-    fn toggle_text_input(&mut self, why: Option<Reason>) {
-        self.state = match self.state {
-            AppState::Main => match why {
-                Some(reason) => {
-                    self.text_input = Some("".to_string());
-                    AppState::TextInput(reason)
-                }
-                None => AppState::TextInput(Reason::Register),
-            },
-            _ => AppState::Main,
+    fn start_text_input(&mut self, reason: Reason) {
+        self.text_input = Some("".to_string());
+        self.state = AppState::TextInput(reason);
+    }
+
+    fn finish_text_input(&mut self, reason: Reason) {
+        self.state = AppState::Main;
+        let value = match self.get_and_clear_text_input() {
+            Some(x) => x,
+            None => return,
         };
+
+        match reason {
+            Reason::Register => {
+                self.pending_username = Some(value);
+                self.start_text_input(Reason::RegisterPassword);
+            }
+            Reason::Login => {
+                self.pending_username = Some(value);
+                self.start_text_input(Reason::LoginPassword);
+            }
+            Reason::RegisterPassword => self.register_user(value),
+            Reason::LoginPassword => self.login(value),
+            Reason::CreateCharacter => {
+                self.pending_character_name = Some(value);
+                self.state = AppState::ClassSelect;
+            }
+            Reason::Invite => self.invite_to_party(value),
+            Reason::RiddleAnswer => self.answer_riddle(value),
+        }
     }
 
     fn exit(&mut self) {
@@ -146,197 +326,1467 @@ impl App {
     }
 
     fn start_register_user(&mut self) {
-        self.toggle_text_input(Some(Reason::Register));
+        self.start_text_input(Reason::Register);
     }
 
-    fn finish_register_user(&mut self) {
-        self.toggle_text_input(None);
-        self.active_user = match self.get_and_clear_text_input() {
-            Some(name) => self.register_user(name),
-            _ => None,
+    fn start_login(&mut self) {
+        self.start_text_input(Reason::Login);
+    }
+
+    fn pending_credentials(&mut self, password: String) -> Credentials {
+        Credentials {
+            username: self.pending_username.take().unwrap_or_default(),
+            password,
+        }
+    }
+
+    /// Registers a new user and logs straight in with the same credentials
+    fn register_user(&mut self, password: String) {
+        let credentials = self.pending_credentials(password);
+
+        let result = self
+            .client
+            .post_register_user(&credentials)
+            .and_then(|_| self.client.post_login(&credentials));
+
+        if let Some(session) = self.report(result) {
+            self.active_user = Some(session.user);
+        }
+    }
+
+    fn login(&mut self, password: String) {
+        let credentials = self.pending_credentials(password);
+
+        let result = self.client.post_login(&credentials);
+        if let Some(session) = self.report(result) {
+            self.active_user = Some(session.user);
+        }
+    }
+
+    fn start_character(&mut self) {
+        self.start_text_input(Reason::CreateCharacter);
+    }
+
+    /// Creates the named character with the class listed under the pressed number key
+    fn finish_character(&mut self, key: char) {
+        let class = match Self::key_index(key).and_then(|i| CharacterClass::ALL.get(i)) {
+            Some(class) => *class,
+            None => return,
+        };
+
+        self.state = AppState::Main;
+        let new_character = NewCharacter {
+            name: self.pending_character_name.take().unwrap_or_default(),
+            class,
         };
+
+        let result = self.client.post_new_character(&new_character);
+        if let Some(new_char) = self.report(result) {
+            self.active_character = Some(new_char);
+        }
     }
 
-    fn register_user(&self, username: String) -> Option<User> {
-        match self.client.post_register_user(username) {
-            Ok(x) => Some(x),
-            _ => None,
+    fn start_character_select(&mut self) {
+        let result = self.client.get_characters();
+        if let Some(characters) = self.report(result) {
+            self.characters = characters;
+            self.state = AppState::CharacterSelect;
         }
     }
 
-    fn register_character(&mut self) {
-        self.active_character = match self.client.post_new_character() {
-            Ok(new_char) => Some(new_char),
-            _ => None,
+    /// Picks the character listed under the pressed number key
+    fn select_character(&mut self, key: char) {
+        if let Some(chr) = Self::key_index(key).and_then(|i| self.characters.get(i)) {
+            self.active_character = Some(chr.clone());
+            self.state = AppState::Main;
+        }
+    }
+
+    fn start_gear(&mut self) {
+        if self.active_character.is_some() {
+            self.state = AppState::Gear;
+        } else {
+            self.error = Some("Select a character to manage its gear".to_string());
+        }
+    }
+
+    /// The inventory items that can be worn, in the order they're listed
+    fn wearable_items(chr: &Character) -> Vec<&Item> {
+        chr.inventory.items.iter().filter(|x| x.kind.slot().is_some()).collect()
+    }
+
+    /// Equips the item listed under the pressed number key
+    fn equip(&mut self, key: char) {
+        let (id, item) = match self.active_character.as_ref().zip(Self::key_index(key)) {
+            Some((chr, i)) => match Self::wearable_items(chr).get(i) {
+                Some(item) => (chr.id, item.name.clone()),
+                None => return,
+            },
+            None => return,
         };
+
+        let result = self.client.post_equip(id, item);
+        if let Some(chr) = self.report(result) {
+            self.update_character(chr);
+        }
     }
 
-    fn start_quest(&mut self) {
-        self.active_quest = match self.client.post_new_quest() {
-            Ok(new_q) => Some(new_q),
-            _ => None,
+    fn unequip(&mut self, slot: EquipSlot) {
+        let id = match &self.active_character {
+            Some(chr) => chr.id,
+            None => return,
+        };
+
+        let result = self.client.post_unequip(id, slot);
+        if let Some(chr) = self.report(result) {
+            self.update_character(chr);
         }
     }
-}
 
-impl Widget for &App {
-    fn render(self, area: Rect, buf: &mut Buffer) {
-        let text_style = Style::default()
-            .fg(Color::Rgb(247, 255, 174))
-            .add_modifier(Modifier::BOLD);
+    /// Swaps in the latest state of the active character
+    fn refresh_characters(&mut self) {
+        let result = self.client.get_characters();
+        if let Some(characters) = self.report(result) {
+            self.active_character = self
+                .active_character
+                .take()
+                .map(|chr| characters.iter().find(|x| x.id == chr.id).cloned().unwrap_or(chr));
+            self.characters = characters;
+        }
+    }
 
-        match self.state {
-            AppState::TextInput(_) => {
-                self.render_input(buf, text_style);
-            }
-            _ => {
-                self.render_main(area, buf, text_style);
-                self.render_stats(buf, text_style);
-                self.render_user(buf, text_style);
-                self.render_quest(buf, text_style);
+    /// Pays for a night at the inn to recover the active character's health and energy
+    fn rest(&mut self) {
+        let id = match &self.active_character {
+            Some(chr) => chr.id,
+            None => {
+                self.error = Some("Select a character to rest".to_string());
+                return;
             }
+        };
+
+        let result = self.client.post_rest(id);
+        if let Some(chr) = self.report(result) {
+            self.update_character(chr);
         }
     }
-}
 
-impl App {
-    fn render_main(&self, area: Rect, buf: &mut Buffer, text_style: Style) {
-        let title = Line::from(" Open Ratventures ".bold());
+    /// Fetches today's stock and opens the shop
+    fn start_shop(&mut self) {
+        if self.active_character.is_none() {
+            self.error = Some("Select a character to go shopping".to_string());
+            return;
+        }
 
-        let instructions = Line::from(vec![
-            " Register: ".into(),
-            Span::styled("<R>", text_style),
-            " New Character: ".into(),
-            Span::styled("<C>", text_style),
-            " Quit: ".into(),
-            Span::styled("<Q>", text_style),
-        ]);
+        let result = self.client.get_shop();
+        if let Some(shop) = self.report(result) {
+            self.shop = Some(shop);
+            self.state = AppState::Shop;
+        }
+    }
 
-        let block = Block::default()
-            .title(title.centered())
-            .title_bottom(instructions.centered())
-            .borders(Borders::ALL)
-            .border_set(border::THICK);
+    /// Buys one of the item offered under the pressed number key
+    fn buy(&mut self, key: char) {
+        let offer = self.shop.as_ref().zip(Self::key_index(key)).and_then(|(shop, i)| shop.stock.get(i));
+        let (id, item) = match (&self.active_character, offer) {
+            (Some(chr), Some(offer)) => (chr.id, offer.item.name.clone()),
+            _ => return,
+        };
 
-        let title = Text::from(vec![Line::from(vec!["Welcome".into()])]);
+        let result = self.client.post_buy(id, item);
+        if let Some(chr) = self.report(result) {
+            self.update_character(chr);
+        }
+    }
 
-        Paragraph::new(title)
-            .centered()
-            .block(block)
-            //.bg(Color::Rgb(116,86,116))
-            .render(area, buf);
+    /// Sells one of the carried item listed under the pressed number key
+    fn sell(&mut self, key: char) {
+        let (id, item) = match self.active_character.as_ref().zip(Self::key_index(key)) {
+            Some((chr, i)) => match chr.inventory.items.get(i) {
+                Some(item) => (chr.id, item.name.clone()),
+                None => return,
+            },
+            None => return,
+        };
+
+        let result = self.client.post_sell(id, item);
+        if let Some(chr) = self.report(result) {
+            self.update_character(chr);
+        }
     }
 
-    fn render_stats(&self, buf: &mut Buffer, text_style: Style) {
-        let stats_block = Block::default()
-            .title(Line::from(" Stats ".bold()))
-            .borders(Borders::ALL)
-            .border_set(border::THICK);
+    fn update_character(&mut self, chr: Character) {
+        if let Some(listed) = self.characters.iter_mut().find(|x| x.id == chr.id) {
+            *listed = chr.clone();
+        }
+        self.active_character = Some(chr);
+    }
 
-        // Only Render Stats if you have any stats
-        let chr = match &self.active_character {
-            Some(c) => c,
+    fn start_party(&mut self) {
+        self.refresh_party();
+        self.state = AppState::Party;
+    }
+
+    fn refresh_party(&mut self) {
+        let result = self.client.get_party();
+        if let Some(lobby) = self.report(result) {
+            self.lobby = lobby;
+        }
+    }
+
+    fn advance_quest(&mut self) {
+        let id = match &self.active_quest {
+            Some(quest) => quest.id,
             None => return,
         };
 
-        let mut health_text = vec![];
+        let result = self.client.post_advance_quest(id);
+        if let Some(quest) = self.report(result) {
+            self.set_quest(quest);
+        }
+    }
 
-        health_text.push(Line::from(vec![
-            "Health: ".into(),
-            Span::styled(chr.unit.stats.health.to_string(), text_style),
-            "/".into(),
-            Span::styled(chr.unit.max_stats.health.to_string(), text_style),
-        ]));
+    /// Gives up on the quest and brings the party home
+    fn abandon_quest(&mut self) {
+        let id = match &self.active_quest {
+            Some(quest) => quest.id,
+            None => return,
+        };
 
-        health_text.push(Line::from(vec![
-            "Energy: ".into(),
-            Span::styled(chr.unit.stats.energy.to_string(), text_style),
-            "/".into(),
-            Span::styled(chr.unit.max_stats.energy.to_string(), text_style),
-        ]));
+        let result = self.client.post_abandon_quest(id);
+        if let Some(quest) = self.report(result) {
+            self.set_quest(quest);
+        }
+    }
 
-        health_text.push(Line::from(vec![
-            "Coins: ".into(),
-            Span::styled(chr.coins.to_string(), text_style),
-        ]));
+    /// Keeps the latest quest state, opening the combat screen whenever the party is in a fight.
+    /// Characters are fetched again once the quest is over, to show what they brought home.
+    fn set_quest(&mut self, quest: Quest) {
+        let already_over = self
+            .active_quest
+            .as_ref()
+            .is_some_and(|x| x.id == quest.id && x.status.is_finished());
+        if quest.status.is_finished() && !already_over {
+            self.refresh_characters();
+        }
 
-        health_text.push(Line::from(vec![
-            "Experience: ".into(),
-            Span::styled(chr.experience.to_string(), text_style),
-            //Span::styled(self.experience.to_string(), text_style,),
-        ]));
-        let stats_rect = Rect::new(5, 6, 50, 7);
+        let talking = matches!(quest.open_encounter, Some(Encounter::NpcEncounter(_)));
+        self.state = match Self::open_combat(&quest) {
+            Some(_) => AppState::Combat,
+            None if matches!(self.state, AppState::Combat) => AppState::Main,
+            None if matches!(self.state, AppState::Dialogue) && !talking => AppState::Main,
+            None => self.state,
+        };
+        self.active_quest = Some(quest);
+    }
 
-        Paragraph::new(health_text)
-            //.scroll((1,0))
-            //.centered()
-            .block(stats_block)
-            .bg(Color::Rgb(116, 86, 116))
-            .render(stats_rect, buf);
+    fn open_combat(quest: &Quest) -> Option<&Combat> {
+        match &quest.open_encounter {
+            Some(Encounter::CombatEncounter(combat)) => Some(combat),
+            _ => None,
+        }
     }
 
-    fn render_user(&self, buf: &mut Buffer, text_style: Style) {
-        let user_block = Block::default()
-            .title(Line::from(" User: ".bold()))
-            .borders(Borders::ALL)
-            .border_set(border::THICK);
+    fn select_target(&mut self, key: char) {
+        let count = self
+            .active_quest
+            .as_ref()
+            .and_then(Self::open_combat)
+            .map_or(0, |x| x.monsters.len());
 
-        let current_user = match &self.active_user {
-            Some(x) => Line::from(vec![
-                "Username: ".into(),
-                Span::styled(&x.username, text_style),
-            ]),
-            None => Line::from(vec!["No active user".into()]),
-        };
-        let user_text = Text::from(vec![current_user]);
-        let user_rect = Rect::new(5, 2, 50, 3);
+        if let Some(index) = Self::key_index(key).filter(|x| *x < count) {
+            self.combat_target = index;
+        }
+    }
 
-        Paragraph::new(user_text)
-            .block(user_block)
-            .bg(Color::Rgb(116, 86, 116))
-            .render(user_rect, buf);
+    fn combat_action(&mut self, action: CombatAction) {
+        self.combat_action_on(action, self.combat_target);
     }
 
-    fn render_input(&self, buf: &mut Buffer, text_style: Style) {
-        let block = Block::default()
-            .title(Line::from(
-                " Input username - Enter to Finish, Esc to stop ".bold(),
-            ))
-            .borders(Borders::ALL)
-            .border_set(border::THICK);
+    fn combat_action_on(&mut self, action: CombatAction, target: usize) {
+        let id = match &self.active_quest {
+            Some(quest) => quest.id,
+            None => return,
+        };
 
-        let current_text = match &self.text_input {
-            Some(x) => Line::from(vec![Span::styled(x, text_style)]),
-            None => Line::from(vec!["Type a username".into()]),
+        let result = self.client.post_combat_action(id, action, target);
+        if let Some(quest) = self.report(result) {
+            self.set_quest(quest);
+        }
+    }
+
+    /// Plays out the turn without waiting on party members who haven't chosen yet
+    fn end_turn(&mut self) {
+        let id = match &self.active_quest {
+            Some(quest) => quest.id,
+            None => return,
         };
-        let text = Text::from(vec![current_text]);
-        let rect = Rect::new(40, 15, 100, 3);
 
-        Paragraph::new(text)
-            .block(block)
-            .bg(Color::Rgb(116, 86, 116))
-            .render(rect, buf);
+        let result = self.client.post_end_turn(id);
+        if let Some(quest) = self.report(result) {
+            self.set_quest(quest);
+        }
     }
 
-    fn render_quest(&self, buf: &mut Buffer, text_style: Style) {
-        let block = Block::default()
-            .title(Line::from(" Quest: ".bold()))
-            .borders(Borders::ALL)
-            .border_set(border::THICK);
+    /// The caller's own character among the quest's members
+    fn quest_member(&self) -> Option<&Character> {
+        let user = self.active_user.as_ref()?;
+        self.active_quest.as_ref()?.members.iter().find(|x| x.owner == user.id)
+    }
 
-        let current_quest = match &self.active_quest {
-            Some(x) => Line::from(vec![
-                " Quest started! ".into(),
-                //Span::styled(& , text_style),
-            ]),
+    /// The items that can be used in a fight, in the order they're listed
+    fn usable_items(chr: &Character) -> Vec<&Item> {
+        chr.inventory.items.iter().filter(|x| x.kind == ItemKind::Consumable).collect()
+    }
+
+    /// Uses the item listed under the pressed number key
+    fn use_item(&mut self, key: char) {
+        let item = match self.quest_member().zip(Self::key_index(key)) {
+            Some((chr, i)) => match Self::usable_items(chr).get(i) {
+                Some(item) => (*item).clone(),
+                None => return,
+            },
             None => return,
         };
-        let text = Text::from(vec![current_quest]);
-        let user_rect = Rect::new(60, 2, 50, 3);
 
-        Paragraph::new(text)
-            .block(block)
-            .bg(Color::Rgb(116, 86, 116))
-            .render(user_rect, buf);
+        self.state = AppState::Combat;
+        self.combat_action(CombatAction::UseItem(item));
+    }
+
+    /// Uses the skill listed under the pressed number key, asking for an ally first if it needs one
+    fn use_skill(&mut self, key: char) {
+        let (index, skill) = match self.quest_member().zip(Self::key_index(key)) {
+            Some((chr, i)) => match chr.class.skills().get(i) {
+                Some(skill) => (i, skill),
+                None => return,
+            },
+            None => return,
+        };
+
+        if skill.target == SkillTarget::Ally {
+            self.state = AppState::AllySelect(index);
+        } else {
+            self.state = AppState::Combat;
+            self.combat_action(CombatAction::UseSkill(skill.name.to_string()));
+        }
+    }
+
+    /// Uses the skill at `index` on the party member listed under the pressed number key
+    fn use_skill_on_ally(&mut self, index: usize, key: char) {
+        let skill = match self.quest_member().and_then(|chr| chr.class.skills().get(index)) {
+            Some(skill) => skill,
+            None => return,
+        };
+
+        let ally = Self::key_index(key).and_then(|i| self.active_quest.as_ref()?.members.get(i));
+        if let Some(target) = ally.map(|x| x.id as usize) {
+            self.state = AppState::Combat;
+            self.combat_action_on(CombatAction::UseSkill(skill.name.to_string()), target);
+        }
+    }
+
+    fn start_riddle_answer(&mut self) {
+        if matches!(self.active_quest.as_ref().and_then(|x| x.open_encounter.as_ref()), Some(Encounter::RiddleEncounter(_))) {
+            self.start_text_input(Reason::RiddleAnswer);
+        } else {
+            self.error = Some("There is no riddle to answer".to_string());
+        }
+    }
+
+    fn answer_riddle(&mut self, answer: String) {
+        let id = match &self.active_quest {
+            Some(quest) => quest.id,
+            None => return,
+        };
+
+        let result = self.client.post_riddle_answer(id, answer);
+        if let Some(quest) = self.report(result) {
+            self.set_quest(quest);
+        }
+    }
+
+    fn start_dialogue(&mut self) {
+        if self.open_npc().is_some() {
+            self.state = AppState::Dialogue;
+        } else {
+            self.error = Some("There is nobody to talk to".to_string());
+        }
+    }
+
+    /// Picks the response listed under the pressed number key
+    fn respond_to_npc(&mut self, key: char) {
+        let (id, choices) = match (&self.active_quest, self.open_npc()) {
+            (Some(quest), Some(npc)) => (quest.id, npc.choices.len()),
+            _ => return,
+        };
+
+        if let Some(choice) = Self::key_index(key).filter(|x| *x < choices) {
+            let result = self.client.post_npc_response(id, choice);
+            if let Some(quest) = self.report(result) {
+                self.set_quest(quest);
+            }
+        }
+    }
+
+    fn start_loot(&mut self) {
+        if self.active_quest.as_ref().is_some_and(|x| !x.loot.is_empty()) {
+            self.state = AppState::Loot;
+        } else {
+            self.error = Some("The party hasn't found any loot to roll for".to_string());
+        }
+    }
+
+    /// Picks the drop listed under the pressed number key
+    fn select_drop(&mut self, key: char) {
+        let drops = self.active_quest.as_ref().map_or(0, |x| x.loot.len());
+        if let Some(index) = Self::key_index(key).filter(|x| *x < drops) {
+            self.loot_selected = index;
+        }
+    }
+
+    fn roll_for_loot(&mut self, choice: RollChoice) {
+        let id = match &self.active_quest {
+            Some(quest) => quest.id,
+            None => return,
+        };
+
+        let result = self.client.post_loot_roll(id, self.loot_selected, choice);
+        if let Some(quest) = self.report(result) {
+            self.set_quest(quest);
+        }
+    }
+
+    fn open_npc(&self) -> Option<&Npc> {
+        match self.active_quest.as_ref().and_then(|x| x.open_encounter.as_ref()) {
+            Some(Encounter::NpcEncounter(npc)) => Some(npc),
+            _ => None,
+        }
+    }
+
+    /// Fetches the latest state of the party's quest from the server. Parties don't outlive
+    /// a server restart, so without one the caller's latest quest still going is picked up.
+    fn refresh_quest(&mut self) {
+        self.refresh_party();
+
+        let id = match self.lobby.party.as_ref().and_then(|x| x.quest_id) {
+            Some(id) => id,
+            None => match &self.active_quest {
+                Some(quest) => quest.id,
+                None => return self.find_quest(),
+            },
+        };
+
+        let result = self.client.get_quest(id);
+        if let Some(quest) = self.report(result) {
+            self.set_quest(quest);
+        }
+    }
+
+    /// Picks up the latest unfinished quest one of the caller's characters is on
+    fn find_quest(&mut self) {
+        let result = self.client.get_quests();
+        if let Some(quest) = self.report(result).and_then(|x| x.into_iter().rev().find(|q| !q.status.is_finished())) {
+            self.set_quest(quest);
+        }
+    }
+
+    /// The id of the character taken along into a party
+    fn party_character(&mut self) -> Option<u32> {
+        let id = self.active_character.as_ref().map(|x| x.id);
+        if id.is_none() {
+            self.error = Some("Select a character before joining a party".to_string());
+        }
+        id
+    }
+
+    fn create_party(&mut self) {
+        if let Some(character_id) = self.party_character() {
+            let result = self.client.post_new_party(character_id);
+            if self.report(result).is_some() {
+                self.refresh_party();
+            }
+        }
+    }
+
+    fn invite_to_party(&mut self, username: String) {
+        self.state = AppState::Party;
+        let result = self.client.post_party_invite(username);
+        if self.report(result).is_some() {
+            self.refresh_party();
+        }
+    }
+
+    /// Accepts the invite listed under the pressed number key
+    fn accept_invite(&mut self, key: char) {
+        let party_id = match Self::key_index(key).and_then(|i| self.lobby.invites.get(i)) {
+            Some(party) => party.id,
+            None => return,
+        };
+
+        if let Some(character_id) = self.party_character() {
+            let result = self.client.post_accept_invite(party_id, character_id);
+            if self.report(result).is_some() {
+                self.refresh_party();
+            }
+        }
+    }
+
+    fn leave_party(&mut self) {
+        let result = self.client.post_leave_party();
+        if let Some(lobby) = self.report(result) {
+            self.lobby = lobby;
+        }
+    }
+
+    fn start_quest(&mut self) {
+        let result = self.client.post_new_quest(None);
+        if let Some(quest) = self.report(result) {
+            self.state = AppState::Main;
+            self.set_quest(quest);
+        }
+    }
+}
+
+impl Widget for &App {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let text_style = Style::default()
+            .fg(Color::Rgb(247, 255, 174))
+            .add_modifier(Modifier::BOLD);
+
+        match self.state {
+            AppState::TextInput(reason) => {
+                self.render_input(buf, text_style, reason);
+            }
+            AppState::CharacterSelect => {
+                self.render_character_select(area, buf, text_style);
+            }
+            AppState::ClassSelect => {
+                self.render_class_select(area, buf, text_style);
+            }
+            AppState::Party => {
+                self.render_party(area, buf, text_style);
+                self.render_error(buf, text_style);
+            }
+            AppState::Combat => {
+                self.render_combat(area, buf, text_style);
+                self.render_error(buf, text_style);
+            }
+            AppState::Gear => {
+                self.render_gear(area, buf, text_style);
+                self.render_error(buf, text_style);
+            }
+            AppState::SkillSelect => {
+                self.render_combat(area, buf, text_style);
+                self.render_skill_select(buf, text_style);
+                self.render_error(buf, text_style);
+            }
+            AppState::AllySelect(_) => {
+                self.render_combat(area, buf, text_style);
+                self.render_ally_select(buf, text_style);
+                self.render_error(buf, text_style);
+            }
+            AppState::ItemSelect => {
+                self.render_combat(area, buf, text_style);
+                self.render_item_select(buf, text_style);
+                self.render_error(buf, text_style);
+            }
+            AppState::Dialogue => {
+                self.render_main(area, buf, text_style);
+                self.render_dialogue(buf, text_style);
+                self.render_error(buf, text_style);
+            }
+            AppState::Loot => {
+                self.render_main(area, buf, text_style);
+                self.render_loot(buf, text_style);
+                self.render_error(buf, text_style);
+            }
+            AppState::Shop | AppState::Sell => {
+                self.render_shop(area, buf, text_style);
+                self.render_error(buf, text_style);
+            }
+            _ => {
+                self.render_main(area, buf, text_style);
+                self.render_stats(buf, text_style);
+                self.render_user(buf, text_style);
+                self.render_quest(buf, text_style);
+                self.render_inventory(buf, text_style);
+                self.render_error(buf, text_style);
+            }
+        }
+    }
+}
+
+impl App {
+    fn render_main(&self, area: Rect, buf: &mut Buffer, text_style: Style) {
+        let title = Line::from(" Open Ratventures ".bold());
+
+        let instructions = Line::from(vec![
+            " Register: ".into(),
+            Span::styled("<R>", text_style),
+            " Login: ".into(),
+            Span::styled("<L>", text_style),
+            " New Character: ".into(),
+            Span::styled("<C>", text_style),
+            " Select Character: ".into(),
+            Span::styled("<S>", text_style),
+            " Party: ".into(),
+            Span::styled("<P>", text_style),
+            " Gear: ".into(),
+            Span::styled("<G>", text_style),
+            " Refresh Quest: ".into(),
+            Span::styled("<F>", text_style),
+            " Quit: ".into(),
+            Span::styled("<Q>", text_style),
+        ]);
+
+        let block = Block::default()
+            .title(title.centered())
+            .title_bottom(instructions.centered())
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let title = Text::from(vec![Line::from(vec!["Welcome".into()])]);
+
+        Paragraph::new(title)
+            .centered()
+            .block(block)
+            //.bg(Color::Rgb(116,86,116))
+            .render(area, buf);
+    }
+
+    fn render_stats(&self, buf: &mut Buffer, text_style: Style) {
+        let stats_block = Block::default()
+            .title(Line::from(" Stats ".bold()))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        // Only Render Stats if you have any stats
+        let chr = match &self.active_character {
+            Some(c) => c,
+            None => return,
+        };
+
+        let health_text = vec![
+            Line::from(vec![
+                Span::styled(&chr.name, text_style),
+                " the ".into(),
+                Span::styled(chr.class.to_string(), text_style),
+            ]),
+            Line::from(vec![
+                "Health: ".into(),
+                Span::styled(chr.unit.stats.health.to_string(), text_style),
+                "/".into(),
+                Span::styled(chr.unit.max_stats.health.to_string(), text_style),
+            ]),
+            Line::from(vec![
+                "Energy: ".into(),
+                Span::styled(chr.unit.stats.energy.to_string(), text_style),
+                "/".into(),
+                Span::styled(chr.unit.max_stats.energy.to_string(), text_style),
+            ]),
+            Line::from(vec![
+                "Attack: ".into(),
+                Span::styled(chr.unit.stats.attack.to_string(), text_style),
+                " Defense: ".into(),
+                Span::styled(chr.unit.stats.defense.to_string(), text_style),
+                " Speed: ".into(),
+                Span::styled(chr.unit.stats.speed.to_string(), text_style),
+            ]),
+            Line::from(vec![
+                "Crit: ".into(),
+                Span::styled(format!("{}%", chr.unit.stats.crit), text_style),
+                " Evasion: ".into(),
+                Span::styled(format!("{}%", chr.unit.stats.evasion), text_style),
+            ]),
+            Line::from(vec![
+                "Coins: ".into(),
+                Span::styled(chr.coins.to_string(), text_style),
+            ]),
+            Line::from(vec![
+                "Experience: ".into(),
+                Span::styled(chr.experience.to_string(), text_style),
+                //Span::styled(self.experience.to_string(), text_style,),
+            ]),
+            Line::from(vec![
+                "Level: ".into(),
+                Span::styled(chr.level().to_string(), text_style),
+                match chr.level_progress() {
+                    Some((current, needed)) => format!(" ({current}/{needed} to next level)").into(),
+                    None => " (max level)".into(),
+                },
+            ]),
+            Line::from(vec![
+                "Shop: ".into(),
+                Span::styled("<H>", text_style),
+                format!(" Rest at the Inn for {} coins: ", ratback::inn::fee(chr)).into(),
+                Span::styled("<I>", text_style),
+            ]),
+        ];
+        let stats_rect = Rect::new(5, 6, 50, 11);
+
+        Paragraph::new(health_text)
+            //.scroll((1,0))
+            //.centered()
+            .block(stats_block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(stats_rect, buf);
+    }
+
+    fn render_user(&self, buf: &mut Buffer, text_style: Style) {
+        let user_block = Block::default()
+            .title(Line::from(" User: ".bold()))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let current_user = match &self.active_user {
+            Some(x) => Line::from(vec![
+                "Username: ".into(),
+                Span::styled(&x.username, text_style),
+            ]),
+            None => Line::from(vec!["No active user".into()]),
+        };
+        let user_text = Text::from(vec![current_user]);
+        let user_rect = Rect::new(5, 2, 50, 3);
+
+        Paragraph::new(user_text)
+            .block(user_block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(user_rect, buf);
+    }
+
+    fn render_input(&self, buf: &mut Buffer, text_style: Style, reason: Reason) {
+        let prompt = match reason {
+            Reason::Register | Reason::Login => "username",
+            Reason::RegisterPassword | Reason::LoginPassword => "password",
+            Reason::CreateCharacter => "character name",
+            Reason::Invite => "username to invite",
+            Reason::RiddleAnswer => "riddle answer",
+        };
+
+        let block = Block::default()
+            .title(Line::from(
+                format!(" Input {prompt} - Enter to Finish, Esc to stop ").bold(),
+            ))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let current_text = match &self.text_input {
+            Some(x) if reason == Reason::RegisterPassword || reason == Reason::LoginPassword => {
+                Line::from(vec![Span::styled("*".repeat(x.len()), text_style)])
+            }
+            Some(x) => Line::from(vec![Span::styled(x, text_style)]),
+            None => Line::from(vec![format!("Type a {prompt}").into()]),
+        };
+        let text = Text::from(vec![current_text]);
+        let rect = Rect::new(40, 15, 100, 3);
+
+        Paragraph::new(text)
+            .block(block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(rect, buf);
+    }
+
+    fn render_quest(&self, buf: &mut Buffer, text_style: Style) {
+        let block = Block::default()
+            .title(Line::from(" Quest: ".bold()))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let quest = match &self.active_quest {
+            Some(x) => x,
+            None => return,
+        };
+
+        let mut lines = vec![Line::from(vec![
+            format!(" Quest #{} ", quest.id).into(),
+            Span::styled(format!("{:?}", quest.status), text_style),
+            format!(" (seed {})", quest.seed).into(),
+        ])];
+
+        if let Some(encounter) = &quest.open_encounter {
+            lines.push(Line::from(vec![
+                format!(" Encounter {}/{}: ", quest.encounter_index + 1, quest.encounters.len()).into(),
+                Span::styled(encounter.name(), text_style),
+            ]));
+
+            if let Encounter::RiddleEncounter(riddle) = encounter {
+                lines.push(Line::from(format!(" \"{}\"", riddle.question)));
+                let state = match riddle.status {
+                    RiddleStatus::Unsolved => format!(" {} attempts left", riddle.attempts_left),
+                    RiddleStatus::Solved => " Solved! Move on to claim the reward".to_string(),
+                    RiddleStatus::Failed => " Out of attempts".to_string(),
+                };
+                lines.push(Line::from(vec![Span::styled(state, text_style)]));
+
+                if !riddle.guesses.is_empty() {
+                    lines.push(Line::from(format!(" Wrong guesses: {}", riddle.guesses.join(", "))));
+                }
+            }
+
+            if let Encounter::NpcEncounter(npc) = encounter {
+                let state = match npc.status {
+                    DialogueStatus::Talking => format!(" {} wants a word", npc.name),
+                    DialogueStatus::Over => format!(" Done talking to {}", npc.name),
+                };
+                lines.push(Line::from(vec![Span::styled(state, text_style)]));
+            }
+        }
+
+        for outcome in quest.history.iter().rev().take(3).rev() {
+            lines.push(Line::from(format!(" {}. {}", outcome.encounter + 1, outcome.description)));
+        }
+
+        if let Some(summary) = &quest.summary {
+            lines.push(Line::from(vec![
+                " Quest over! Earned ".into(),
+                Span::styled(format!("{} coins", summary.coins), text_style),
+                " and ".into(),
+                Span::styled(format!("{} experience", summary.experience), text_style),
+            ]));
+        }
+
+        let rolling = quest.loot.iter().filter(|x| x.status == LootStatus::Rolling).count();
+        if rolling > 0 {
+            lines.push(Line::from(vec![Span::styled(format!(" {rolling} drops to roll for"), text_style)]));
+        }
+
+        for level_up in &quest.level_ups {
+            lines.push(Line::from(vec![
+                format!(" {} reached ", level_up.name).into(),
+                Span::styled(format!("level {}", level_up.level), text_style),
+            ]));
+        }
+
+        if !quest.status.is_finished() {
+            lines.push(Line::from(vec![
+                " Next Encounter: ".into(),
+                Span::styled("<N>", text_style),
+                " Give Up: ".into(),
+                Span::styled("<X>", text_style),
+            ]));
+            lines.push(Line::from(vec![
+                " Answer Riddle: ".into(),
+                Span::styled("<Y>", text_style),
+                " Talk: ".into(),
+                Span::styled("<T>", text_style),
+                " Loot: ".into(),
+                Span::styled("<O>", text_style),
+            ]));
+        }
+
+        // Riddles can run longer than the panel is wide, so count the wrapped rows
+        let rows: usize = lines.iter().map(|x| x.width().div_ceil(48).max(1)).sum();
+        let rect = Rect::new(60, 2, 50, rows as u16 + 2);
+
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .wrap(Wrap { trim: false })
+            .bg(Color::Rgb(116, 86, 116))
+            .render(rect, buf);
+    }
+
+    fn render_inventory(&self, buf: &mut Buffer, text_style: Style) {
+        let block = Block::default()
+            .title(Line::from(" Inventory ".bold()))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let chr = match &self.active_character {
+            Some(c) => c,
+            None => return,
+        };
+
+        let mut lines: Vec<Line> = chr
+            .inventory
+            .items
+            .iter()
+            .map(|item| {
+                Line::from(vec![
+                    Span::styled(format!("{}x ", item.quantity), text_style),
+                    Span::styled(item.name.clone(), Self::rarity_style(item.rarity)),
+                    format!(" ({:?})", item.kind).into(),
+                ])
+            })
+            .collect();
+
+        if lines.is_empty() {
+            lines.push(Line::from("Empty"));
+        }
+
+        let rect = Rect::new(5, 22, 50, lines.len() as u16 + 2);
+
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(rect, buf);
+    }
+
+    fn render_item_select(&self, buf: &mut Buffer, text_style: Style) {
+        let block = Block::default()
+            .title(Line::from(" Use Item - Number to pick, Esc to stop ".bold()))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let items = match self.quest_member() {
+            Some(chr) => Self::usable_items(chr),
+            None => return,
+        };
+
+        let mut lines: Vec<Line> = items
+            .iter()
+            .enumerate()
+            .take(9)
+            .map(|(i, item)| {
+                Line::from(vec![
+                    Span::styled(format!("<{}> ", i + 1), text_style),
+                    format!(
+                        "{}x {} - restores {} health, {} energy",
+                        item.quantity, item.name, item.effect.health, item.effect.energy
+                    )
+                    .into(),
+                ])
+            })
+            .collect();
+
+        if lines.is_empty() {
+            lines.push(Line::from("Nothing usable left"));
+        }
+
+        let rect = Rect::new(60, 4, 50, lines.len() as u16 + 2);
+
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(rect, buf);
+    }
+
+    fn render_gear(&self, area: Rect, buf: &mut Buffer, text_style: Style) {
+        let instructions = Line::from(vec![
+            " Equip: ".into(),
+            Span::styled("<1-9>", text_style),
+            " Unequip Weapon: ".into(),
+            Span::styled("<W>", text_style),
+            " Armor: ".into(),
+            Span::styled("<A>", text_style),
+            " Trinket: ".into(),
+            Span::styled("<T>", text_style),
+            " Back: ".into(),
+            Span::styled("<Esc>", text_style),
+        ]);
+
+        let block = Block::default()
+            .title(Line::from(" Gear ".bold()).centered())
+            .title_bottom(instructions.centered())
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let chr = match &self.active_character {
+            Some(c) => c,
+            None => return,
+        };
+
+        let describe = |item: &Item| {
+            let bonus = item.effect;
+            format!(
+                "{} (attack {:+}, defense {:+}, health {:+}, energy {:+})",
+                item.name, bonus.attack, bonus.defense, bonus.health, bonus.energy
+            )
+        };
+
+        let mut lines = vec![Line::from(format!("{} is wearing:", chr.name))];
+        for (label, slot) in [("Weapon", EquipSlot::Weapon), ("Armor", EquipSlot::Armor), ("Trinket", EquipSlot::Trinket)] {
+            let worn = chr.equipment.slot(slot).map_or("nothing".to_string(), describe);
+            lines.push(Line::from(vec![
+                format!("  {label}: ").into(),
+                Span::styled(worn, text_style),
+            ]));
+        }
+
+        lines.push(Line::from(""));
+        lines.push(Line::from("Carried gear:"));
+        for (i, item) in Self::wearable_items(chr).into_iter().enumerate().take(9) {
+            lines.push(Line::from(vec![
+                Span::styled(format!("  <{}> ", i + 1), text_style),
+                format!("{:?}: {}", item.kind, describe(item)).into(),
+            ]));
+        }
+
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(area, buf);
+    }
+
+    fn render_shop(&self, area: Rect, buf: &mut Buffer, text_style: Style) {
+        let selling = matches!(self.state, AppState::Sell);
+
+        let instructions = Line::from(vec![
+            if selling { " Sell: ".into() } else { " Buy: ".into() },
+            Span::styled("<1-9>", text_style),
+            if selling { " Buy Instead: ".into() } else { " Sell Instead: ".into() },
+            Span::styled(if selling { "<B>" } else { "<S>" }, text_style),
+            " Back: ".into(),
+            Span::styled("<Esc>", text_style),
+        ]);
+
+        let block = Block::default()
+            .title(Line::from(" Shop ".bold()).centered())
+            .title_bottom(instructions.centered())
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let (chr, shop) = match self.active_character.as_ref().zip(self.shop.as_ref()) {
+            Some(x) => x,
+            None => return,
+        };
+
+        let mut lines = vec![Line::from(vec![
+            format!("{} has ", chr.name).into(),
+            Span::styled(format!("{} coins", chr.coins), text_style),
+        ])];
+        lines.push(Line::from(""));
+
+        let listed: Vec<(&Item, u32)> = if selling {
+            lines.push(Line::from("The shop pays:"));
+            chr.inventory.items.iter().map(|x| (x, shop::sell_price(x))).collect()
+        } else {
+            lines.push(Line::from(format!("Today's stock (day {}):", shop.day)));
+            shop.stock.iter().map(|x| (&x.item, x.price)).collect()
+        };
+
+        for (i, (item, price)) in listed.into_iter().enumerate().take(9) {
+            let carried = if selling { format!(" x{}", item.quantity) } else { String::new() };
+            lines.push(Line::from(vec![
+                Span::styled(format!("  <{}> ", i + 1), text_style),
+                Span::styled(format!("{}{carried}", item.name), Self::rarity_style(item.rarity)),
+                format!(" ({:?}, {:?}) ", item.kind, item.rarity).into(),
+                Span::styled(format!("{price} coins"), text_style),
+            ]));
+        }
+
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(area, buf);
+    }
+
+    /// The status effects on someone in the fight, as tags to put after their name
+    fn status_labels(combat: &Combat, target: Combatant) -> String {
+        combat.effects_on(target).map(|x| format!(" [{}]", x.label())).collect()
+    }
+
+    fn render_skill_select(&self, buf: &mut Buffer, text_style: Style) {
+        let block = Block::default()
+            .title(Line::from(" Use Skill - Number to pick, Esc to stop ".bold()))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let (chr, combat) = match self.quest_member().zip(self.active_quest.as_ref().and_then(Self::open_combat)) {
+            Some(x) => x,
+            None => return,
+        };
+
+        let lines: Vec<Line> = chr
+            .class
+            .skills()
+            .iter()
+            .enumerate()
+            .map(|(i, skill)| {
+                let cooldown = match combat.cooldown(chr.id, skill.name) {
+                    Some(turn) => format!(" (ready on turn {turn})"),
+                    None => String::new(),
+                };
+
+                Line::from(vec![
+                    Span::styled(format!("<{}> {}", i + 1, skill.name), text_style),
+                    format!(" - {} energy{cooldown}: {}", skill.energy, skill.description).into(),
+                ])
+            })
+            .collect();
+
+        let rect = Rect::new(30, 4, 80, lines.len() as u16 + 2);
+
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(rect, buf);
+    }
+
+    fn render_dialogue(&self, buf: &mut Buffer, text_style: Style) {
+        let npc = match self.open_npc() {
+            Some(x) => x,
+            None => return,
+        };
+
+        let block = Block::default()
+            .title(Line::from(format!(" {} - Number to respond, Esc to stop ", npc.name).bold()))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let mut lines: Vec<Line> = npc
+            .transcript
+            .iter()
+            .rev()
+            .take(6)
+            .rev()
+            .map(|x| Line::from(format!(" {x}")))
+            .collect();
+
+        match npc.status {
+            DialogueStatus::Talking => {
+                lines.push(Line::from(vec![Span::styled(format!(" {}: {}", npc.name, npc.text), text_style)]));
+                for (i, choice) in npc.choices.iter().enumerate() {
+                    lines.push(Line::from(vec![Span::styled(format!(" <{}>", i + 1), text_style), format!(" {choice}").into()]));
+                }
+            }
+            DialogueStatus::Over => {
+                lines.push(Line::from(vec![Span::styled(" The conversation is over, move on to continue", text_style)]));
+            }
+        }
+
+        // Strangers can be long-winded, so count the wrapped rows
+        let rows: usize = lines.iter().map(|x| x.width().div_ceil(78).max(1)).sum();
+        let rect = Rect::new(30, 4, 80, rows as u16 + 2);
+
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .wrap(Wrap { trim: false })
+            .bg(Color::Rgb(116, 86, 116))
+            .render(rect, buf);
+    }
+
+    fn rarity_style(rarity: Rarity) -> Style {
+        let color = match rarity {
+            Rarity::Common => Color::White,
+            Rarity::Uncommon => Color::Rgb(130, 220, 120),
+            Rarity::Rare => Color::Rgb(110, 170, 255),
+            Rarity::Epic => Color::Rgb(220, 140, 255),
+        };
+        Style::default().fg(color)
+    }
+
+    fn render_loot(&self, buf: &mut Buffer, text_style: Style) {
+        let block = Block::default()
+            .title(Line::from(" Loot - Number to pick, <N>eed <G>reed <P>ass, Esc to stop ".bold()))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let quest = match &self.active_quest {
+            Some(x) => x,
+            None => return,
+        };
+        let name_of = |id: u32| quest.members.iter().find(|x| x.id == id).map_or("?", |x| x.name.as_str());
+
+        let lines: Vec<Line> = quest
+            .loot
+            .iter()
+            .enumerate()
+            .map(|(i, drop)| {
+                let marker = if i == self.loot_selected { ">" } else { " " };
+                let state = match drop.status {
+                    LootStatus::Rolling => {
+                        let rolls: Vec<String> = drop
+                            .rolls
+                            .iter()
+                            .map(|x| format!("{} {:?} {}", name_of(x.character_id), x.choice, x.roll))
+                            .collect();
+                        format!(" - rolling ({}/{}) {}", drop.rolls.len(), quest.members.len(), rolls.join(", "))
+                    }
+                    LootStatus::Won(id) => format!(" - won by {}", name_of(id)),
+                    LootStatus::Passed => " - left behind".to_string(),
+                };
+
+                Line::from(vec![
+                    Span::styled(format!("{marker}<{}> ", i + 1), text_style),
+                    Span::styled(drop.item.name.clone(), Self::rarity_style(drop.item.rarity)),
+                    format!(" ({:?}){state}", drop.item.rarity).into(),
+                ])
+            })
+            .collect();
+
+        let rect = Rect::new(30, 4, 80, lines.len() as u16 + 2);
+
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(rect, buf);
+    }
+
+    fn render_ally_select(&self, buf: &mut Buffer, text_style: Style) {
+        let block = Block::default()
+            .title(Line::from(" Pick an Ally - Number to pick, Esc to go back ".bold()))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let quest = match &self.active_quest {
+            Some(x) => x,
+            None => return,
+        };
+
+        let lines: Vec<Line> = quest
+            .members
+            .iter()
+            .enumerate()
+            .map(|(i, member)| {
+                Line::from(vec![
+                    Span::styled(format!("<{}> {}", i + 1, member.name), text_style),
+                    format!(" - Health {}/{}", member.unit.stats.health, member.unit.max_stats.health).into(),
+                ])
+            })
+            .collect();
+
+        let rect = Rect::new(60, 4, 50, lines.len() as u16 + 2);
+
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(rect, buf);
+    }
+
+    fn render_error(&self, buf: &mut Buffer, text_style: Style) {
+        let block = Block::default()
+            .title(Line::from(" Error: ".bold()))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let error = match &self.error {
+            Some(x) => Line::from(vec![Span::styled(x, text_style)]),
+            None => return,
+        };
+        let rect = Rect::new(5, 18, 105, 3);
+
+        Paragraph::new(Text::from(vec![error]))
+            .block(block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(rect, buf);
+    }
+
+    fn render_character_select(&self, area: Rect, buf: &mut Buffer, text_style: Style) {
+        let block = Block::default()
+            .title(Line::from(" Select Character - Number to pick, Esc to stop ".bold()))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let mut lines: Vec<Line> = self
+            .characters
+            .iter()
+            .enumerate()
+            .take(9)
+            .map(|(i, chr)| {
+                Line::from(vec![
+                    Span::styled(format!("<{}> ", i + 1), text_style),
+                    format!(
+                        "{} the level {} {} - Health {}/{} Energy {}/{}",
+                        chr.name,
+                        chr.level(),
+                        chr.class,
+                        chr.unit.stats.health,
+                        chr.unit.max_stats.health,
+                        chr.unit.stats.energy,
+                        chr.unit.max_stats.energy
+                    )
+                    .into(),
+                ])
+            })
+            .collect();
+
+        if lines.is_empty() {
+            lines.push(Line::from("No characters yet, press <C> on the main screen to create one"));
+        }
+
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(area, buf);
+    }
+
+    fn render_class_select(&self, area: Rect, buf: &mut Buffer, text_style: Style) {
+        let block = Block::default()
+            .title(Line::from(" Choose a Class - Number to pick, Esc to stop ".bold()))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let name = self.pending_character_name.clone().unwrap_or_default();
+        let mut lines = vec![Line::from(format!("What kind of rat is {name}?")), Line::from("")];
+
+        for (i, class) in CharacterClass::ALL.iter().enumerate() {
+            let stats = class.base_stats();
+            let items: Vec<String> = class.starting_items().into_iter().map(|x| x.name).collect();
+
+            lines.push(Line::from(vec![
+                Span::styled(format!("<{}> {class}", i + 1), text_style),
+                format!(
+                    " - Health {} Energy {} - Starts with {}",
+                    stats.health,
+                    stats.energy,
+                    items.join(", ")
+                )
+                .into(),
+            ]));
+        }
+
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(area, buf);
+    }
+
+    fn render_party(&self, area: Rect, buf: &mut Buffer, text_style: Style) {
+        let instructions = Line::from(vec![
+            " New Party: ".into(),
+            Span::styled("<N>", text_style),
+            " Invite: ".into(),
+            Span::styled("<I>", text_style),
+            " Accept Invite: ".into(),
+            Span::styled("<1-9>", text_style),
+            " Leave: ".into(),
+            Span::styled("<L>", text_style),
+            " Refresh: ".into(),
+            Span::styled("<R>", text_style),
+            " Start Quest: ".into(),
+            Span::styled("<A>", text_style),
+            " Back: ".into(),
+            Span::styled("<Esc>", text_style),
+        ]);
+
+        let block = Block::default()
+            .title(Line::from(" Party Lobby ".bold()).centered())
+            .title_bottom(instructions.centered())
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let mut lines = vec![];
+
+        match &self.lobby.party {
+            Some(party) => {
+                lines.push(Line::from(format!(
+                    "Party #{} ({}/{MAX_PARTY_SIZE})",
+                    party.id,
+                    party.members.len()
+                )));
+                for member in &party.members {
+                    let role = if member.user_id == party.leader { " (leader)" } else { "" };
+                    lines.push(Line::from(vec![
+                        "  ".into(),
+                        Span::styled(&member.username, text_style),
+                        role.into(),
+                    ]));
+                }
+                for username in &party.invited {
+                    lines.push(Line::from(format!("  {username} (invited)")));
+                }
+            }
+            None => lines.push(Line::from("You are not in a party")),
+        }
+
+        lines.push(Line::from(""));
+        lines.push(Line::from("Invites:"));
+        if self.lobby.invites.is_empty() {
+            lines.push(Line::from("  No invites"));
+        }
+        for (i, party) in self.lobby.invites.iter().enumerate().take(9) {
+            let leader = party
+                .members
+                .iter()
+                .find(|x| x.user_id == party.leader)
+                .map(|x| x.username.as_str())
+                .unwrap_or("someone");
+            lines.push(Line::from(vec![
+                Span::styled(format!("  <{}> ", i + 1), text_style),
+                format!("Join {leader}'s party").into(),
+            ]));
+        }
+
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(area, buf);
+    }
+
+    fn render_combat(&self, area: Rect, buf: &mut Buffer, text_style: Style) {
+        let instructions = Line::from(vec![
+            " Attack: ".into(),
+            Span::styled("<A>", text_style),
+            " Use Item: ".into(),
+            Span::styled("<U>", text_style),
+            " Skills: ".into(),
+            Span::styled("<K>", text_style),
+            " Target: ".into(),
+            Span::styled(format!("<1-{MAX_COMBAT_ENEMIES}>"), text_style),
+            " End Turn: ".into(),
+            Span::styled("<E>", text_style),
+            " Refresh: ".into(),
+            Span::styled("<R>", text_style),
+            " Next Encounter: ".into(),
+            Span::styled("<N>", text_style),
+            " Back: ".into(),
+            Span::styled("<Esc>", text_style),
+        ]);
+
+        let (quest, combat) = match self.active_quest.as_ref().and_then(|q| Self::open_combat(q).map(|c| (q, c))) {
+            Some(x) => x,
+            None => return,
+        };
+
+        let status = match combat.status {
+            CombatStatus::Ongoing => format!(" Combat - Turn {} ", combat.turn + 1),
+            CombatStatus::Victory => " Combat - Victory! ".to_string(),
+            CombatStatus::Wipe => " Combat - Defeat ".to_string(),
+        };
+
+        let block = Block::default()
+            .title(Line::from(status.bold()).centered())
+            .title_bottom(instructions.centered())
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        let mut lines = vec![Line::from("Party:")];
+        for member in &quest.members {
+            let ready = if combat.pending.iter().any(|x| x.character_id == member.id) { " (ready)" } else { "" };
+            lines.push(Line::from(vec![
+                "  ".into(),
+                Span::styled(&member.name, text_style),
+                format!(
+                    " - Health {}/{} Energy {}/{}{ready}",
+                    member.unit.stats.health,
+                    member.unit.max_stats.health,
+                    member.unit.stats.energy,
+                    member.unit.max_stats.energy
+                )
+                .into(),
+                Self::status_labels(combat, Combatant::Member(member.id)).into(),
+            ]));
+        }
+
+        lines.push(Line::from(""));
+        lines.push(Line::from("Monsters:"));
+        for (i, monster) in combat.monsters.iter().enumerate() {
+            let marker = if i == self.combat_target { ">" } else { " " };
+            let health = if monster.fled {
+                " - Fled".to_string()
+            } else if monster.unit.stats.health > 0 {
+                format!(" - Health {}/{}", monster.unit.stats.health, monster.unit.max_stats.health)
+            } else {
+                " - Defeated".to_string()
+            };
+            lines.push(Line::from(vec![
+                Span::styled(format!(" {marker}<{}> ", i + 1), text_style),
+                monster.name.clone().into(),
+                format!(" ({}, hits for {})", monster.family, monster.damage).into(),
+                health.into(),
+                Self::status_labels(combat, Combatant::Monster(i)).into(),
+            ]));
+        }
+
+        lines.push(Line::from(""));
+        lines.push(Line::from("Log:"));
+        for entry in combat.log.iter().rev().take(8).rev() {
+            lines.push(Line::from(format!("  [{}] {}", entry.turn, entry.message)));
+        }
+
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .bg(Color::Rgb(116, 86, 116))
+            .render(area, buf);
     }
 }